@@ -1,6 +1,7 @@
 use std::{
     fs,
     io::{self, Read},
+    path::PathBuf,
 };
 
 use clap::Parser;
@@ -9,13 +10,20 @@ use hql::{html, querier};
 #[derive(Debug, Parser)]
 #[command(author, version, about = "A human-friendly Html Query Language\n\nIt has three possible mode to receive html, with priority from high to low: file, inline argument and stdin", long_about = None)]
 struct Cli {
-    /// Html Query Language
+    /// Html Query Language. Several pipelines can be separated by `;` outside of backtick strings,
+    /// then each runs on the whole document in order
     #[arg(long, value_name = "HQL")]
     hql: String,
 
-    /// Input HTML file needed to be searched
+    /// Input HTML file needed to be searched. It can be repeated or be a directory, then each file
+    /// is queried independently and output lines are prefixed with the file name
     #[arg(short, long, value_name = "FILE")]
-    file: Option<String>,
+    file: Vec<String>,
+
+    /// Print only the number of results instead of each result, one line per pipeline. When querying
+    /// multiple files, the counts of each file are prefixed with the file name
+    #[arg(long)]
+    count: bool,
 
     /// Inline HTML string
     document: Option<String>,
@@ -24,18 +32,43 @@ struct Cli {
 fn main() {
     tracing_subscriber::fmt::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        // stdout only carries results, so that it can be parsed
+        .with_writer(io::stderr)
         .init();
 
     let cli = Cli::parse();
 
-    let q = querier::Querier::try_parse(&cli.hql)
-        .unwrap_or_else(|e| panic!("failed to parse hql: {}", e));
+    let q = split_pipelines(&cli.hql)
+        .into_iter()
+        .map(|p| {
+            querier::Querier::try_parse(p).unwrap_or_else(|e| panic!("failed to parse hql: {}", e))
+        })
+        .collect::<Vec<_>>();
+
+    // keep the single file behavior: no prefix and fail on unreadable file
+    if let [file] = cli.file.as_slice() {
+        if !fs::metadata(file).is_ok_and(|m| m.is_dir()) {
+            let doc_str = fs::read_to_string(file)
+                .unwrap_or_else(|e| panic!("file {} not found: {}", file, e));
+            query_and_print(&q, cli.count, &doc_str, None);
+            return;
+        }
+    }
+
+    if !cli.file.is_empty() {
+        for file in expand_files(&cli.file) {
+            match fs::read_to_string(&file) {
+                Ok(doc_str) => {
+                    query_and_print(&q, cli.count, &doc_str, Some(&file.to_string_lossy()))
+                }
+                Err(e) => eprintln!("failed to read file {}: {}", file.display(), e),
+            }
+        }
+        return;
+    }
 
     let mut doc_str = String::new();
-    if let Some(file) = cli.file {
-        doc_str =
-            fs::read_to_string(&file).unwrap_or_else(|e| panic!("file {} not found: {}", file, e));
-    } else if let Some(doc) = cli.document {
+    if let Some(doc) = cli.document {
         doc_str = doc;
     } else {
         io::stdin()
@@ -43,9 +76,66 @@ fn main() {
             .unwrap_or_else(|e| panic!("failed to read stdin to string: {}", e));
     }
 
-    let doc = html::Html::parse_document(&doc_str, false);
+    query_and_print(&q, cli.count, &doc_str, None);
+}
 
-    q.query_document(&doc)
-        .into_iter()
-        .for_each(|n| println!("{}", n));
+/// Split hql on `;` outside of backtick strings, where a backslash escapes the next char. Blank
+/// pipelines, like after a trailing `;`, are dropped.
+fn split_pipelines(hql: &str) -> Vec<&str> {
+    let mut pipelines = vec![];
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in hql.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '`' => quoted = !quoted,
+            ';' if !quoted => {
+                pipelines.push(&hql[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pipelines.push(&hql[start..]);
+    pipelines.retain(|p| !p.trim().is_empty());
+    pipelines
+}
+
+/// Replace directories with the files directly inside them, in name order
+fn expand_files(files: &[String]) -> Vec<PathBuf> {
+    files
+        .iter()
+        .flat_map(|f| match fs::read_dir(f) {
+            Ok(entries) => {
+                let mut paths = entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.is_file())
+                    .collect::<Vec<_>>();
+                paths.sort();
+                paths
+            }
+            Err(_) => vec![PathBuf::from(f)],
+        })
+        .collect()
+}
+
+fn query_and_print(qs: &[querier::Querier], count: bool, doc_str: &str, prefix: Option<&str>) {
+    let doc = html::Html::parse_document(doc_str, false);
+
+    for q in qs {
+        let nodes = q.query_document(&doc);
+
+        if count {
+            match prefix {
+                Some(p) => println!("{}: {}", p, nodes.len()),
+                None => println!("{}", nodes.len()),
+            }
+            continue;
+        }
+
+        nodes.into_iter().for_each(|n| match prefix {
+            Some(p) => println!("{}: {}", p, n),
+            None => println!("{}", n),
+        });
+    }
 }
@@ -0,0 +1,137 @@
+use std::{fs, process::Command};
+
+fn hql(args: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_hql"))
+        .args(args)
+        .output()
+        .expect("failed to run hql");
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Split output lines into file name prefix and trimmed value
+fn prefixed_lines(stdout: &str) -> Vec<(&str, &str)> {
+    stdout
+        .lines()
+        .map(|l| {
+            let (file, value) = l.split_once(": ").unwrap();
+            (file, value.trim())
+        })
+        .collect()
+}
+
+#[test]
+fn test_multiple_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.html");
+    let b = dir.path().join("b.html");
+    fs::write(&a, r#"<a href="/a">a</a>"#).unwrap();
+    fs::write(&b, r#"<a href="/b1">b1</a><a href="/b2">b2</a>"#).unwrap();
+    let missing = dir.path().join("missing.html");
+
+    let (a, b, missing) = (
+        a.to_str().unwrap(),
+        b.to_str().unwrap(),
+        missing.to_str().unwrap(),
+    );
+
+    // unreadable files are reported and skipped
+    let (stdout, stderr) = hql(&[
+        "--hql",
+        "@path(`//a`) | #attr(`href`)",
+        "-f",
+        a,
+        "-f",
+        missing,
+        "-f",
+        b,
+    ]);
+    assert_eq!(
+        prefixed_lines(&stdout),
+        vec![(a, "/a"), (b, "/b1"), (b, "/b2")]
+    );
+    assert!(stderr.contains(missing));
+
+    // directories are expanded to files in name order
+    let (stdout, _) = hql(&[
+        "--hql",
+        "@path(`//a`) | #attr(`href`)",
+        "-f",
+        dir.path().to_str().unwrap(),
+    ]);
+    assert_eq!(
+        prefixed_lines(&stdout),
+        vec![(a, "/a"), (b, "/b1"), (b, "/b2")]
+    );
+
+    // a single file is printed without prefix
+    let (stdout, _) = hql(&["--hql", "@path(`//a`) | #attr(`href`)", "-f", a]);
+    assert_eq!(stdout.trim(), "/a");
+}
+
+#[test]
+fn test_count() {
+    let (stdout, _) = hql(&[
+        "--hql",
+        "@path(`//li`)",
+        "--count",
+        "<ul><li>a</li><li>b</li><li>c</li></ul>",
+    ]);
+    assert_eq!(stdout, "3\n");
+
+    let (stdout, _) = hql(&["--hql", "@path(`//li`)", "--count", "<p></p>"]);
+    assert_eq!(stdout, "0\n");
+
+    // one count per pipeline, and `;` inside backticks does not split
+    let (stdout, _) = hql(&[
+        "--hql",
+        "@path(`//li`); @path(`//p`) ;#const(`a;\\`;b`);",
+        "--count",
+        "<ul><li>a</li><li>b</li></ul>",
+    ]);
+    assert_eq!(stdout, "2\n0\n1\n");
+
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.html");
+    let b = dir.path().join("b.html");
+    fs::write(&a, "<li>a</li>").unwrap();
+    fs::write(&b, "<li>b1</li><li>b2</li>").unwrap();
+
+    let (stdout, _) = hql(&[
+        "--hql",
+        "@path(`//li`)",
+        "--count",
+        "-f",
+        dir.path().to_str().unwrap(),
+    ]);
+    assert_eq!(
+        prefixed_lines(&stdout),
+        vec![(a.to_str().unwrap(), "1"), (b.to_str().unwrap(), "2")]
+    );
+
+    let (stdout, _) = hql(&[
+        "--hql",
+        "@path(`//li`);@path(`//p`)",
+        "--count",
+        "-f",
+        dir.path().to_str().unwrap(),
+    ]);
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+    assert_eq!(
+        prefixed_lines(&stdout),
+        vec![(a, "1"), (a, "0"), (b, "2"), (b, "0")]
+    );
+}
+
+#[test]
+fn test_multiple_pipelines() {
+    // results of each pipeline are printed in order
+    let (stdout, _) = hql(&[
+        "--hql",
+        "@path(`//li`) | #text(); #const(`x;y`)",
+        "<ul><li>a</li><li>b</li></ul>",
+    ]);
+    assert_eq!(stdout, "a\nb\nx;y\n");
+}
@@ -0,0 +1,118 @@
+use std::fs;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hql::{
+    html::{ElementOrTextRef, Html},
+    querier::Querier,
+};
+
+// sof.html, the page the benchmarks were requested against, is not in the repository, so a
+// checked-in page is used instead
+const DOCUMENT: &str = "../docs/test-html/ytb.html";
+
+const PIPELINES: [&str; 4] = [
+    "@flat()",
+    "@path(`//a`) | @attr(`href`) | #attr(`href`)",
+    "@path(`//div.style-scope`) | #text() | #trim()",
+    "@path(`//body//div/span`) | @child(0)",
+];
+
+fn bench_parse(c: &mut Criterion) {
+    let s = fs::read_to_string(DOCUMENT).unwrap();
+
+    c.bench_function("parse_document", |b| {
+        b.iter(|| Html::parse_document(black_box(&s), false))
+    });
+}
+
+fn bench_query(c: &mut Criterion) {
+    let s = fs::read_to_string(DOCUMENT).unwrap();
+
+    // parse once and share the document among all queriers
+    let doc = Html::parse_document(&s, false);
+
+    let mut group = c.benchmark_group("query_document");
+    for hql in PIPELINES {
+        let q = Querier::try_parse(hql).unwrap_or_else(|e| panic!("failed to parse hql: {}", e));
+        group.bench_function(hql, |b| b.iter(|| q.query_document(black_box(&doc))));
+    }
+    group.finish();
+}
+
+fn bench_text(c: &mut Criterion) {
+    let s = fs::read_to_string(DOCUMENT).unwrap();
+    let doc = Html::parse_document(&s, false);
+
+    let q = Querier::try_parse("@path(`//div`)").unwrap();
+    let divs = q
+        .query_document(&doc)
+        .into_iter()
+        .filter_map(|n| match n {
+            ElementOrTextRef::Element(e) => Some(e),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // text of nested divs overlaps, so aggregating it repeatedly is quadratic in the depth
+    let mut group = c.benchmark_group("text");
+    group.bench_function("traverse", |b| {
+        b.iter(|| {
+            divs.iter()
+                .map(|e| e.text().map(|t| t.text().len()).sum::<usize>())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| divs.iter().map(|e| e.text_content().len()).sum::<usize>())
+    });
+    group.finish();
+}
+
+fn bench_children(c: &mut Criterion) {
+    // a list-heavy page, where child counts of the same lists are queried repeatedly
+    let s = "<ul>".to_string() + &"<li>x</li> ".repeat(50) + "</ul>";
+    let doc = Html::parse_fragment(&s.repeat(1000), false);
+
+    let q = Querier::try_parse("@path(`//ul`)").unwrap();
+    let lists = q
+        .query_document(&doc)
+        .into_iter()
+        .filter_map(|n| match n {
+            ElementOrTextRef::Element(e) => Some(e),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("children");
+    group.bench_function("traverse", |b| {
+        b.iter(|| {
+            lists
+                .iter()
+                .map(|e| {
+                    e.clone()
+                        .children(false)
+                        .filter(|c| matches!(c, ElementOrTextRef::Element(_)))
+                        .count()
+                })
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| lists.iter().map(|e| e.child_element_count()).sum::<usize>())
+    });
+    group.finish();
+
+    let q = Querier::try_parse("@path(`//ul`) | @minChildren(50)").unwrap();
+    c.bench_function("min_children", |b| {
+        b.iter(|| q.query_document(black_box(&doc)))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_query,
+    bench_text,
+    bench_children
+);
+criterion_main!(benches);
@@ -0,0 +1,175 @@
+use html5ever::{tendril::StrTendril, Attribute, LocalName, QualName};
+
+use crate::tree::{NodeID, Tree};
+
+use super::{
+    dom::{DomNode, Element, ProcessingInstruction, Text},
+    Html, HtmlParseOptions,
+};
+
+/// HtmlBuilder constructs an `Html` tree directly, without going through html5ever. It is mainly
+/// for tests, where a small and exact DOM is wanted.
+///
+/// A builder with `element` set is an element node, otherwise its children are appended to the
+/// root directly.
+///
+/// ```
+/// use hql::html::{Html, HtmlBuilder};
+///
+/// let doc = Html::builder()
+///     .element("ul")
+///     .attr("id", "x")
+///     .child(HtmlBuilder::new().element("li").text("a"))
+///     .child(HtmlBuilder::new().element("li").text("b"))
+///     .build();
+/// assert_eq!(doc.tag_histogram()["li"], 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct HtmlBuilder {
+    name: Option<QualName>,
+    attrs: Vec<Attribute>,
+    children: Vec<Child>,
+}
+
+#[derive(Debug)]
+enum Child {
+    Node(HtmlBuilder),
+    Text(StrTendril),
+    ProcessingInstruction(StrTendril, StrTendril),
+}
+
+impl HtmlBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// make the node an HTML element with tag name
+    pub fn element(mut self, tag: &str) -> Self {
+        self.name = Some(QualName::new(None, ns!(html), LocalName::from(tag)));
+        self
+    }
+
+    pub fn attr(mut self, name: &str, value: &str) -> Self {
+        self.attrs.push(Attribute {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            value: StrTendril::from_slice(value),
+        });
+        self
+    }
+
+    pub fn child(mut self, child: HtmlBuilder) -> Self {
+        self.children.push(Child::Node(child));
+        self
+    }
+
+    /// append a text child. Adjacent text children are not merged.
+    pub fn text(mut self, text: &str) -> Self {
+        self.children
+            .push(Child::Text(StrTendril::from_slice(text)));
+        self
+    }
+
+    /// append a processing instruction child like `<?target data?>`, which the HTML parser never
+    /// creates
+    pub fn processing_instruction(mut self, target: &str, data: &str) -> Self {
+        self.children.push(Child::ProcessingInstruction(
+            StrTendril::from_slice(target),
+            StrTendril::from_slice(data),
+        ));
+        self
+    }
+
+    /// build a fragment rooted tree, like `Html::parse_fragment`
+    pub fn build(self) -> Html {
+        let mut html = Html::new_fragment(&HtmlParseOptions::default());
+        let root = html.nodes.root_ref().unwrap().id;
+        self.append_to(&mut html.nodes, root);
+        html
+    }
+
+    fn append_to(self, tree: &mut Tree<DomNode>, parent: NodeID) {
+        let parent = match self.name {
+            Some(name) => {
+                tree.append_child(parent, DomNode::Element(Element::new(name, self.attrs)))
+                    .unwrap()
+                    .id
+            }
+            None => parent,
+        };
+
+        for child in self.children {
+            match child {
+                Child::Node(b) => b.append_to(tree, parent),
+                Child::Text(t) => {
+                    tree.append_child(parent, DomNode::Text(Text::new(t)));
+                }
+                Child::ProcessingInstruction(target, data) => {
+                    tree.append_child(
+                        parent,
+                        DomNode::ProcessingInstruction(ProcessingInstruction::new(target, data)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        html::{Html, QueryResult},
+        querier::Querier,
+    };
+
+    use super::HtmlBuilder;
+
+    fn li(text: &str) -> HtmlBuilder {
+        HtmlBuilder::new().element("li").text(text)
+    }
+
+    fn query_texts(hql: &str, doc: &Html) -> Vec<String> {
+        let q = Querier::try_parse(hql).unwrap();
+        q.query_document(doc)
+            .iter()
+            .map(|n| QueryResult::from(n).text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_builder() {
+        let doc = Html::builder()
+            .element("ul")
+            .child(li("a").attr("class", "x"))
+            .child(li("b"))
+            .child(li("c").attr("class", "x"))
+            .build();
+
+        assert_eq!(
+            query_texts("@path(`//li`) | @class(`x`)", &doc),
+            vec!["a", "c"]
+        );
+        assert_eq!(
+            query_texts("@path(`/ul`) | @child(0) | @following(`li`)", &doc),
+            vec!["b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_builder_dump_tree() {
+        let doc = Html::builder()
+            .child(
+                HtmlBuilder::new()
+                    .element("p")
+                    .attr("id", "x")
+                    .text("a")
+                    .child(HtmlBuilder::new().element("br")),
+            )
+            .child(HtmlBuilder::new().element("p"))
+            .build();
+
+        assert_eq!(
+            doc.dump_tree(),
+            "0 Fragment\n  1 <p id=\"x\">\n    2 Text \"a\"\n    3 <br>\n  4 <p>\n"
+        );
+    }
+}
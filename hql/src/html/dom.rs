@@ -2,6 +2,7 @@ use std::{
     cell::OnceCell,
     collections::{HashMap, HashSet},
     fmt::{Debug, Display},
+    ops::Range,
 };
 
 use html5ever::{tendril::StrTendril, Attribute, ExpandedName, LocalName, QualName};
@@ -84,7 +85,7 @@ impl Display for DomNode {
             DomNode::Fragment => write!(f, "Fragment"),
             DomNode::DocType(d) => write!(f, "{d}"),
             DomNode::Element(e) => write!(f, "{e}"),
-            DomNode::Text(t) => write!(f, " {t}"),
+            DomNode::Text(t) => write!(f, "{t}"),
             DomNode::Comment(c) => write!(f, "{c}"),
             DomNode::ProcessingInstruction(pi) => write!(f, "{pi}"),
         }
@@ -106,6 +107,10 @@ impl DocType {
             system_id,
         }
     }
+
+    pub fn name(&self) -> &StrTendril {
+        &self.name
+    }
 }
 
 impl Display for DocType {
@@ -126,6 +131,13 @@ pub struct Element {
     // cache id and classes
     id: OnceCell<Option<StrTendril>>,
     classes: OnceCell<HashSet<LocalName>>,
+    // cache aggregated text of the subtree, filled by ElementRef::text_content
+    text: OnceCell<StrTendril>,
+    // cache number of element children, filled by ElementRef::child_element_count
+    child_elements: OnceCell<usize>,
+    // byte range of the element in the parsed input and its markup sharing the input buffer,
+    // recorded by HtmlParseOptions::track_raw_source
+    source: Option<(Range<usize>, StrTendril)>,
 }
 
 impl Display for Element {
@@ -145,10 +157,17 @@ impl Element {
             attrs: attrs.into_iter().map(|a| (a.name, a.value)).collect(),
             id: OnceCell::new(),
             classes: OnceCell::new(),
+            text: OnceCell::new(),
+            child_elements: OnceCell::new(),
+            source: None,
         }
     }
 
-    pub fn expanded_name(&self) -> ExpandedName {
+    pub fn name(&self) -> &QualName {
+        &self.name
+    }
+
+    pub fn expanded_name(&self) -> ExpandedName<'_> {
         self.name.expanded()
     }
 
@@ -173,6 +192,51 @@ impl Element {
         })
     }
 
+    pub(crate) fn text_or_init<F: FnOnce() -> StrTendril>(&self, f: F) -> &StrTendril {
+        self.text.get_or_init(f)
+    }
+
+    pub(crate) fn child_elements_or_init<F: FnOnce() -> usize>(&self, f: F) -> usize {
+        *self.child_elements.get_or_init(f)
+    }
+
+    /// Lowercase the tag and attribute names, which are only mixed case in foreign content
+    pub(crate) fn lowercase_names(&mut self) {
+        fn lowercase(name: &QualName) -> Option<QualName> {
+            name.local.bytes().any(|b| b.is_ascii_uppercase()).then(|| {
+                QualName::new(
+                    name.prefix.clone(),
+                    name.ns.clone(),
+                    name.local.to_ascii_lowercase(),
+                )
+            })
+        }
+
+        if let Some(name) = lowercase(&self.name) {
+            self.name = name;
+        }
+        if self.attrs.keys().any(|k| lowercase(k).is_some()) {
+            self.attrs = std::mem::take(&mut self.attrs)
+                .into_iter()
+                .map(|(k, v)| (lowercase(&k).unwrap_or(k), v))
+                .collect();
+        }
+    }
+
+    pub(crate) fn set_source(&mut self, range: Range<usize>, raw: StrTendril) {
+        self.source = Some((range, raw));
+    }
+
+    /// Byte range of the element in the parsed input, see `HtmlParseOptions::track_raw_source`
+    pub fn source_range(&self) -> Option<Range<usize>> {
+        self.source.as_ref().map(|(r, _)| r.clone())
+    }
+
+    /// Original markup of the element, see `HtmlParseOptions::track_raw_source`
+    pub fn raw_source(&self) -> Option<&str> {
+        self.source.as_ref().map(|(_, raw)| raw.as_ref())
+    }
+
     pub(crate) fn add_attrs(&mut self, attrs: Vec<Attribute>) {
         attrs.into_iter().for_each(|attr| {
             self.attrs.entry(attr.name).or_insert(attr.value);
@@ -186,6 +250,10 @@ impl Element {
         })
     }
 
+    pub fn attrs(&self) -> impl Iterator<Item = (&QualName, &StrTendril)> {
+        self.attrs.iter()
+    }
+
     pub fn get_attrs(&self, name: &QualName) -> Option<&StrTendril> {
         info!("attrs: {:?}", self.attrs);
         self.attrs.get(name)
@@ -211,6 +279,7 @@ impl Text {
     }
 }
 
+/// Write the raw text without escaping, see `ElementOrTextRef::to_html` for the HTML of text
 impl Display for Text {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.text)
@@ -226,6 +295,10 @@ impl Comment {
     pub fn new(comment: StrTendril) -> Self {
         Self { comment }
     }
+
+    pub fn comment(&self) -> &StrTendril {
+        &self.comment
+    }
 }
 
 impl Display for Comment {
@@ -244,6 +317,14 @@ impl ProcessingInstruction {
     pub fn new(target: StrTendril, data: StrTendril) -> Self {
         Self { target, data }
     }
+
+    pub fn target(&self) -> &StrTendril {
+        &self.target
+    }
+
+    pub fn data(&self) -> &StrTendril {
+        &self.data
+    }
 }
 
 impl Display for ProcessingInstruction {
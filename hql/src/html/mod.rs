@@ -1,24 +1,135 @@
 //! The HTML DOM tree
 //!
 //! Parse HTML as a DOM tree, using [html5ever](https://docs.rs/html5ever).
+pub mod builder;
 #[allow(dead_code)]
 pub mod dom;
+pub mod serialize;
 pub mod tree_sink;
 
-use std::{borrow::Cow, fmt::Display, rc::Rc};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    ops::Range,
+    rc::Rc,
+};
 
 use html5ever::{
-    driver,
-    tendril::{StrTendril, TendrilSink},
-    tokenizer::TokenizerOpts,
-    tree_builder::{QuirksMode, TreeBuilderOpts},
-    ExpandedName, ParseOpts, QualName,
+    driver::{self, Parser},
+    tendril::{stream::Utf8LossyDecoder, ByteTendril, StrTendril, TendrilSink},
+    tokenizer::{BufferQueue, Tokenizer, TokenizerOpts, TokenizerResult},
+    tree_builder::{create_element, QuirksMode, TreeBuilder, TreeBuilderOpts, TreeSink},
+    ExpandedName, LocalName, ParseOpts, QualName,
 };
 use tracing::warn;
 
-use crate::tree::{ChildrenTraverse, Node, PreOrderTraverse, Tree};
+use crate::{
+    querier::Querier,
+    selector::HqlError,
+    tree::{ChildrenTraverse, Node, PreOrderTraverse, Tree},
+};
+
+pub use crate::tree::NodeID;
+
+pub use self::builder::HtmlBuilder;
+pub use self::serialize::HtmlSerializeOptions;
+
+use self::{
+    dom::{DomNode, Element, ProcessingInstruction, Text},
+    tree_sink::{SourceTokenSink, SourceTracker},
+};
+
+/// Options of parsing HTML, built in builder style from `HtmlParseOptions::default()`
+///
+/// html5ever lowercases tag and attribute names of HTML elements. Names of foreign content, i.e.
+/// elements inside `<svg>` or `<math>`, keep their standard mixed case like `clipPath` and
+/// `viewBox`, unless `lowercase_foreign_names` is set. Either way, selectors compare tag names
+/// case-insensitively, see `ElementRef::is_tag`.
+#[derive(Debug, Clone)]
+pub struct HtmlParseOptions {
+    exact_errors: bool,
+    scripting_enabled: bool,
+    drop_whitespace: bool,
+    lowercase_foreign_names: bool,
+    track_raw_source: bool,
+}
+
+impl Default for HtmlParseOptions {
+    fn default() -> Self {
+        Self {
+            exact_errors: false,
+            scripting_enabled: true,
+            drop_whitespace: false,
+            lowercase_foreign_names: false,
+            track_raw_source: false,
+        }
+    }
+}
+
+impl HtmlParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report parse errors in detail, which is slower. Default false
+    pub fn exact_errors(mut self, exact_errors: bool) -> Self {
+        self.exact_errors = exact_errors;
+        self
+    }
+
+    /// Parse as if scripting is enabled, which treats `<noscript>` contents as raw text. Default true
+    pub fn scripting_enabled(mut self, scripting_enabled: bool) -> Self {
+        self.scripting_enabled = scripting_enabled;
+        self
+    }
+
+    /// Drop text nodes consisting solely of whitespace, except inside `<pre>`, `<textarea>` and
+    /// raw text elements like `<script>`. Default false
+    ///
+    /// Text may arrive at the tree sink in several chunks, so the nodes are dropped once parsing
+    /// finishes rather than when they are created.
+    pub fn drop_whitespace(mut self, drop_whitespace: bool) -> Self {
+        self.drop_whitespace = drop_whitespace;
+        self
+    }
+
+    /// Lowercase tag and attribute names of foreign content, so that output like serialized HTML
+    /// and `#tagName()` only contains lowercase names. Default false
+    pub fn lowercase_foreign_names(mut self, lowercase_foreign_names: bool) -> Self {
+        self.lowercase_foreign_names = lowercase_foreign_names;
+        self
+    }
+
+    /// Record the byte range of each element in the input, so that `ElementOrTextRef::raw_source`
+    /// returns its exact original markup, from the start tag up to the end of the end tag, with
+    /// quoting, entities and whitespace untouched. Default false
+    ///
+    /// The markup shares one copy of the whole input, which stays alive as long as any element of
+    /// the document, or a copy of it, does. Elements implied by the parser, like `<tbody>` or the
+    /// `<body>` of a fragment, have no markup. Elements without end tag, like `<p>` closed by the
+    /// next `<p>`, end where the token closing them starts, or at the end of input. The input is
+    /// fed to the parser in small chunks to track positions, so parsing is slower, and
+    /// `Html::parser` does not support it.
+    pub fn track_raw_source(mut self, track_raw_source: bool) -> Self {
+        self.track_raw_source = track_raw_source;
+        self
+    }
 
-use self::dom::{DomNode, Text};
+    fn parse_opts(&self) -> ParseOpts {
+        ParseOpts {
+            tokenizer: TokenizerOpts {
+                exact_errors: self.exact_errors,
+                ..TokenizerOpts::default()
+            },
+            tree_builder: TreeBuilderOpts {
+                exact_errors: self.exact_errors,
+                scripting_enabled: self.scripting_enabled,
+                ..TreeBuilderOpts::default()
+            },
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Html {
@@ -27,70 +138,294 @@ pub struct Html {
     quirks_mode: QuirksMode,
 
     errors: Vec<Cow<'static, str>>,
+
+    opts: HtmlParseOptions,
+
+    // positions of the input being parsed with HtmlParseOptions::track_raw_source
+    source: Option<SourceTracker>,
 }
 
 impl Html {
-    fn new_document() -> Self {
+    fn new_document(opts: &HtmlParseOptions) -> Self {
         Self {
             nodes: Tree::new(DomNode::Document),
             quirks_mode: QuirksMode::NoQuirks,
             errors: vec![],
+            opts: opts.clone(),
+            source: None,
         }
     }
 
-    fn new_fragment() -> Self {
+    fn new_fragment(opts: &HtmlParseOptions) -> Self {
         Self {
             nodes: Tree::new(DomNode::Fragment),
             quirks_mode: QuirksMode::NoQuirks,
             errors: Vec::new(),
+            opts: opts.clone(),
+            source: None,
         }
     }
 
+    /// Build a new fragment whose top-level nodes are copies of nodes, which may come from other
+    /// documents. Phantom nodes are copied as well.
+    pub fn from_nodes<'a: 'b, 'b>(
+        nodes: impl IntoIterator<Item = &'b ElementOrTextRef<'a>>,
+    ) -> Self {
+        let mut html = Self::new_fragment(&HtmlParseOptions::default());
+        let root = html.nodes.root_ref().unwrap().id;
+        append_copies(&mut html.nodes, root, nodes);
+        html
+    }
+
+    /// Build a tree directly instead of parsing, see `HtmlBuilder`
+    pub fn builder() -> HtmlBuilder {
+        HtmlBuilder::new()
+    }
+
     pub fn parse_document(doc: &str, exact_errors: bool) -> Self {
-        driver::parse_document(
-            Self::new_document(),
-            ParseOpts {
-                tokenizer: TokenizerOpts {
-                    exact_errors,
-                    ..TokenizerOpts::default()
-                },
-                tree_builder: TreeBuilderOpts {
-                    exact_errors,
-                    ..TreeBuilderOpts::default()
-                },
-            },
-        )
-        .one(doc)
+        Self::parse_document_with(doc, &HtmlParseOptions::new().exact_errors(exact_errors))
+    }
+
+    pub fn parse_document_with(doc: &str, opts: &HtmlParseOptions) -> Self {
+        if opts.track_raw_source {
+            let parse_opts = opts.parse_opts();
+            let builder = TreeBuilder::new(Self::new_document(opts), parse_opts.tree_builder);
+            return Self::parse_tracked(builder, parse_opts.tokenizer, doc);
+        }
+        driver::parse_document(Self::new_document(opts), opts.parse_opts()).one(doc)
+    }
+
+    /// Feed input to the tree builder in chunks split before each `<` and after each `>`, so that
+    /// a tag token is complete at the end of a chunk, and the tree sink knows where each token
+    /// starts and ends
+    fn parse_tracked(
+        mut builder: TreeBuilder<NodeID, Html>,
+        opts: TokenizerOpts,
+        input: &str,
+    ) -> Self {
+        let source = StrTendril::from_slice(input);
+        builder.sink.source = Some(SourceTracker::new(source.clone()));
+        let mut tokenizer = Tokenizer::new(SourceTokenSink { builder }, opts);
+
+        let mut ends = input
+            .bytes()
+            .enumerate()
+            .filter_map(|(i, b)| match b {
+                b'<' if i > 0 => Some(i),
+                b'>' => Some(i + 1),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        ends.dedup();
+        ends.push(input.len());
+
+        let mut queue = BufferQueue::new();
+        let mut start = 0;
+        for end in ends {
+            if end == start {
+                continue;
+            }
+            if let Some(s) = tokenizer.sink.builder.sink.source.as_mut() {
+                s.advance(end);
+            }
+            // both ends are at ASCII chars or the end, so the chunk is valid UTF-8
+            queue.push_back(source.subtendril(start as u32, (end - start) as u32));
+            while let TokenizerResult::Script(_) = tokenizer.feed(&mut queue) {}
+            start = end;
+        }
+
+        tokenizer.end();
+        TreeSink::finish(tokenizer.sink.builder.sink)
+    }
+
+    /// Create an incremental parser. Feed it with chunks of UTF-8 bytes by `DocumentParser::process`
+    /// and get the parsed document by `DocumentParser::finish`.
+    pub fn parser(exact_errors: bool) -> DocumentParser {
+        let opts = HtmlParseOptions::new().exact_errors(exact_errors);
+        DocumentParser {
+            inner: driver::parse_document(Self::new_document(&opts), opts.parse_opts()).from_utf8(),
+        }
     }
 
     pub fn parse_fragment(frag: &str, exact_errors: bool) -> Self {
-        driver::parse_fragment(
-            Self::new_fragment(),
-            ParseOpts {
-                tokenizer: TokenizerOpts {
-                    exact_errors,
-                    ..TokenizerOpts::default()
-                },
-                tree_builder: TreeBuilderOpts {
-                    exact_errors,
-                    ..TreeBuilderOpts::default()
-                },
-            },
+        Self::parse_fragment_with(frag, &HtmlParseOptions::new().exact_errors(exact_errors))
+    }
+
+    pub fn parse_fragment_with(frag: &str, opts: &HtmlParseOptions) -> Self {
+        Self::parse_fragment_in(
+            frag,
             QualName::new(None, ns!(html), local_name!("body")),
+            opts,
+        )
+    }
+
+    /// Parse fragment as the content of the context element, instead of `<body>` like
+    /// `parse_fragment`. The context changes how the fragment is parsed, e.g. `<td>` is dropped
+    /// outside of a table, so parse table rows with a `<tr>` context.
+    pub fn parse_fragment_with_context(frag: &str, context: QualName, exact_errors: bool) -> Self {
+        Self::parse_fragment_in(
+            frag,
+            context,
+            &HtmlParseOptions::new().exact_errors(exact_errors),
+        )
+    }
+
+    fn parse_fragment_in(frag: &str, context: QualName, opts: &HtmlParseOptions) -> Self {
+        if opts.track_raw_source {
+            // same as driver::parse_fragment, which builds its own token sink
+            let parse_opts = opts.parse_opts();
+            let mut html = Self::new_fragment(opts);
+            let context = create_element(&mut html, context, Vec::new());
+            let builder =
+                TreeBuilder::new_for_fragment(html, context, None, parse_opts.tree_builder);
+            let tok_opts = TokenizerOpts {
+                initial_state: Some(builder.tokenizer_state_for_context_elem()),
+                ..parse_opts.tokenizer
+            };
+            return Self::parse_tracked(builder, tok_opts, frag);
+        }
+        driver::parse_fragment(
+            Self::new_fragment(opts),
+            opts.parse_opts(),
+            context,
             Vec::new(),
         )
         .one(frag)
     }
+
+    /// Detach whitespace-only text nodes, skipping subtrees which preserve whitespace
+    fn drop_whitespace_text(&mut self) {
+        let root = self.nodes.root_ref().unwrap();
+        let blanks = PreOrderTraverse::new_pruned(&self.nodes, root, preserves_whitespace)
+            .filter(|(n, _)| {
+                n.data
+                    .as_text()
+                    .is_some_and(|t| t.text().trim_matches(is_html_whitespace).is_empty())
+            })
+            .map(|(n, _)| n.id)
+            .collect::<Vec<_>>();
+
+        for id in blanks {
+            self.nodes.detach(id);
+        }
+    }
+
+    fn lowercase_foreign_names(&mut self) {
+        for id in 0..self.nodes.nodes().len() {
+            if let Some(DomNode::Element(e)) =
+                self.nodes.node_mut_ref(id.into()).map(|n| &mut n.data)
+            {
+                e.lowercase_names();
+            }
+        }
+    }
+
+    /// Replace the data of node id in place, like renaming an element, keeping its position and
+    /// children. Return the old data, or None if id does not exist.
+    pub fn replace_node_data(&mut self, id: NodeID, data: DomNode) -> Option<DomNode> {
+        self.nodes.replace_data(id, data)
+    }
+
+    /// Parse errors collected during parsing
+    pub fn errors(&self) -> &[Cow<'static, str>] {
+        &self.errors
+    }
+
+    /// Options the document is parsed with
+    pub fn parse_options(&self) -> &HtmlParseOptions {
+        &self.opts
+    }
+}
+
+/// DocumentParser parses a document incrementally from chunks of UTF-8 bytes.
+///
+/// A chunk can end in the middle of a multi-byte character. Invalid UTF-8 sequences are replaced
+/// with U+FFFD.
+pub struct DocumentParser {
+    inner: Utf8LossyDecoder<Parser<Html>>,
+}
+
+impl DocumentParser {
+    pub fn process(&mut self, chunk: &[u8]) {
+        self.inner.process(ByteTendril::from_slice(chunk))
+    }
+
+    pub fn finish(self) -> Html {
+        self.inner.finish()
+    }
 }
 
 impl Html {
-    pub fn root(&self) -> ElementOrTextRef {
+    pub fn root(&self) -> ElementOrTextRef<'_> {
         ElementOrTextRef::Element(ElementRef {
             node: self.nodes.root_ref().unwrap(),
             tree: &self.nodes,
         })
     }
 
+    /// Return the node of id, like an id from `ElementOrTextRef::document_position`, to query from it
+    /// with `Querier::query_node`. Document and fragment nodes, like the root, are returned as
+    /// elements. None is returned for invalid ids, for other kinds of nodes like comments, and for
+    /// nodes detached from the document, like whitespace dropped by `HtmlParseOptions`.
+    pub fn node_ref_by_id(&self, id: NodeID) -> Option<ElementOrTextRef<'_>> {
+        let node = self.nodes.node_ref(id)?;
+        let top = std::iter::successors(Some(node), |n| self.nodes.parent_ref(n.id)).last()?;
+        if top.id != self.nodes.root_ref()?.id {
+            return None;
+        }
+
+        let tree = &self.nodes;
+        match node.data {
+            DomNode::Document | DomNode::Fragment => {
+                Some(ElementOrTextRef::Element(ElementRef { tree, node }))
+            }
+            _ => element_or_text((node, tree)),
+        }
+    }
+
+    /// Parse hql and query the document with it in one call. Build a `Querier` instead to run the
+    /// same hql on many documents.
+    ///
+    /// ```
+    /// use hql::html::Html;
+    ///
+    /// let doc = Html::parse_document(r#"<a href="/x">x</a><p><a href="/y">y</a></p>"#, false);
+    /// let links = doc.select("@path(`//a`) | #attr(`href`)").unwrap();
+    /// assert_eq!(links.len(), 2);
+    /// assert_eq!(links[1].as_text_cow(), "/y");
+    ///
+    /// assert!(doc.select("@flat(").is_err());
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn select(&self, hql: &str) -> Result<Vec<ElementOrTextRef<'_>>, HqlError> {
+        Ok(Querier::try_parse(hql)?.query_document(self))
+    }
+
+    /// Like `select`, but return only the first result, evaluating selectors lazily like
+    /// `Querier::query_first`
+    #[allow(clippy::result_large_err)]
+    pub fn select_first(&self, hql: &str) -> Result<Option<ElementOrTextRef<'_>>, HqlError> {
+        Ok(Querier::try_parse(hql)?.query_first(self))
+    }
+
+    /// Count occurrences of each element tag name in the document
+    pub fn tag_histogram(&self) -> HashMap<String, usize> {
+        PreOrderTraverse::new(&self.nodes, self.nodes.root_ref().unwrap())
+            .filter_map(|(n, _)| n.data.as_element())
+            .fold(HashMap::new(), |mut m, e| {
+                *m.entry(e.expanded_name().local.to_string()).or_insert(0) += 1;
+                m
+            })
+    }
+
+    /// Collect all distinct attribute names used by elements in the document
+    pub fn attribute_names(&self) -> HashSet<String> {
+        PreOrderTraverse::new(&self.nodes, self.nodes.root_ref().unwrap())
+            .filter_map(|(n, _)| n.data.as_element())
+            .flat_map(|e| e.attrs().map(|(k, _)| k.local.to_string()))
+            .collect()
+    }
+
     pub fn traverse_all(&self) -> Vec<DomNode> {
         PreOrderTraverse::new(&self.nodes, self.nodes.root_ref().unwrap())
             .map(move |(n, _)| n.data.clone())
@@ -98,6 +433,47 @@ impl Html {
     }
 }
 
+impl Html {
+    /// Dump the tree structure for troubleshooting, one node per line with its id and indented by
+    /// depth, like `  1 <html>`. Template contents and comments are included, and text is quoted.
+    pub fn dump_tree(&self) -> String {
+        let mut out = String::new();
+        let mut stack = vec![(self.nodes.root_ref().unwrap(), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let desc = match &node.data {
+                DomNode::Document => "Document".to_string(),
+                DomNode::Fragment => "Fragment".to_string(),
+                DomNode::DocType(d) => format!("DocType {}", d.name()),
+                DomNode::Element(e) => {
+                    let mut attrs = e
+                        .attrs()
+                        .map(|(k, v)| format!(" {}={:?}", k.local, v.as_ref()))
+                        .collect::<Vec<_>>();
+                    attrs.sort();
+                    format!("<{}{}>", e.name().local, attrs.concat())
+                }
+                DomNode::Text(t) => format!("Text {:?}", t.text().as_ref()),
+                DomNode::Comment(c) => format!("Comment {:?}", c.comment().as_ref()),
+                DomNode::ProcessingInstruction(pi) => {
+                    format!(
+                        "ProcessingInstruction {} {:?}",
+                        pi.target(),
+                        pi.data().as_ref()
+                    )
+                }
+            };
+            out.push_str(&format!("{}{} {}\n", "  ".repeat(depth), node.id, desc));
+
+            stack.extend(
+                ChildrenTraverse::new(&self.nodes, node, true).map(|(n, _)| (n, depth + 1)),
+            );
+        }
+        out
+    }
+}
+
+/// Dump all nodes in creation order for debugging, which is not HTML. See `Html::html` for the
+/// serialized document.
 impl Display for Html {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for n in self.nodes.nodes() {
@@ -107,12 +483,93 @@ impl Display for Html {
     }
 }
 
+/// Contents of `<template>` are stored in a Fragment node under the template element. They are
+/// opaque to subtree traversal and text extraction, matching browser semantics.
+fn is_template_contents(node: &Node<DomNode>) -> bool {
+    node.data.is_fragment()
+}
+
+/// Whether whitespace in the subtree of node is significant
+fn preserves_whitespace(node: &Node<DomNode>) -> bool {
+    node.data.as_element().is_some_and(|e| {
+        matches!(
+            e.name().expanded(),
+            expanded_name!(html "pre")
+                | expanded_name!(html "listing")
+                | expanded_name!(html "textarea")
+                | expanded_name!(html "title")
+                | expanded_name!(html "script")
+                | expanded_name!(html "style")
+                | expanded_name!(html "xmp")
+                | expanded_name!(html "iframe")
+                | expanded_name!(html "noembed")
+                | expanded_name!(html "noframes")
+                | expanded_name!(html "noscript")
+                | expanded_name!(html "plaintext")
+        )
+    })
+}
+
+/// ASCII whitespace as defined by the HTML standard, which excludes non-breaking spaces
+fn is_html_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\x0C' | '\r')
+}
+
+/// Whether node is a block-level element breaking the flow of text, like `<p>` or `<li>`
+fn is_block(node: &Node<DomNode>) -> bool {
+    node.data.as_element().is_some_and(|e| {
+        matches!(
+            e.name().expanded(),
+            expanded_name!(html "address")
+                | expanded_name!(html "article")
+                | expanded_name!(html "aside")
+                | expanded_name!(html "blockquote")
+                | expanded_name!(html "caption")
+                | expanded_name!(html "dd")
+                | expanded_name!(html "details")
+                | expanded_name!(html "div")
+                | expanded_name!(html "dl")
+                | expanded_name!(html "dt")
+                | expanded_name!(html "fieldset")
+                | expanded_name!(html "figcaption")
+                | expanded_name!(html "figure")
+                | expanded_name!(html "footer")
+                | expanded_name!(html "form")
+                | expanded_name!(html "h1")
+                | expanded_name!(html "h2")
+                | expanded_name!(html "h3")
+                | expanded_name!(html "h4")
+                | expanded_name!(html "h5")
+                | expanded_name!(html "h6")
+                | expanded_name!(html "header")
+                | expanded_name!(html "li")
+                | expanded_name!(html "main")
+                | expanded_name!(html "nav")
+                | expanded_name!(html "ol")
+                | expanded_name!(html "p")
+                | expanded_name!(html "pre")
+                | expanded_name!(html "section")
+                | expanded_name!(html "summary")
+                | expanded_name!(html "table")
+                | expanded_name!(html "tr")
+                | expanded_name!(html "ul")
+        )
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct ElementRef<'a> {
     tree: &'a Tree<DomNode>,
     node: &'a Node<DomNode>,
 }
 
+/// Element refs are equal when they point to the same node of the same document
+impl<'a> PartialEq for ElementRef<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.tree, other.tree) && self.node.id == other.node.id
+    }
+}
+
 impl<'a> Display for ElementRef<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.node)
@@ -120,49 +577,313 @@ impl<'a> Display for ElementRef<'a> {
 }
 
 impl<'a> ElementRef<'a> {
-    pub fn expanded_name(&self) -> ExpandedName {
-        self.node.data.as_element().unwrap().expanded_name()
+    /// The element data. An ElementRef points to an element node, except the one returned by
+    /// `Html::root`, which wraps the document or fragment root to start querying from. The root
+    /// behaves like an element without name, attributes or ids.
+    fn element(&self) -> Option<&'a Element> {
+        debug_assert!(
+            matches!(
+                self.node.data,
+                DomNode::Element(_) | DomNode::Document | DomNode::Fragment
+            ),
+            "ElementRef points to a non-element node {:?}",
+            self.node.id
+        );
+        self.node.data.as_element()
+    }
+
+    /// The tag name, or None for the document root
+    pub fn name(&self) -> Option<&'a QualName> {
+        self.element().map(Element::name)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if it is the document root returned by `Html::root`, which has no name. Use `name`
+    /// or `is_tag` when the node may be the root.
+    pub fn expanded_name(&self) -> ExpandedName<'_> {
+        self.name()
+            .expect("the document root has no expanded name")
+            .expanded()
+    }
+
+    /// Whether the local tag name equals tag, ignoring ASCII case. Selectors match tags by it, so
+    /// that both `clipPath` and `clippath` match a SVG `<clipPath>`.
+    pub fn is_tag(&self, tag: &str) -> bool {
+        self.name()
+            .is_some_and(|n| n.local.eq_str_ignore_ascii_case(tag))
     }
 
+    /// Get value of attribute name.
+    ///
+    /// html5ever lowercases attribute names of HTML elements, so they match name ignoring ASCII
+    /// case, like `dataValue` matching `datavalue`. Attributes of foreign elements like SVG keep
+    /// their case, like `viewBox`, and match name exactly.
     pub fn get_attr(&self, name: &QualName) -> Option<&StrTendril> {
-        self.node.data.as_element().unwrap().get_attrs(name)
+        let e = self.element()?;
+        e.get_attrs(name).or_else(|| {
+            if e.name().ns != ns!(html) || !name.local.bytes().any(|b| b.is_ascii_uppercase()) {
+                return None;
+            }
+            e.get_attrs(&QualName::new(
+                name.prefix.clone(),
+                name.ns.clone(),
+                name.local.to_ascii_lowercase(),
+            ))
+        })
+    }
+
+    /// Get value of attribute name in no namespace, like `get_attr` without building a `QualName`.
+    /// The name matches ignoring ASCII case on HTML elements as in `get_attr`. Use `get_attr` for
+    /// namespaced attributes like `xlink:href`.
+    ///
+    /// ```
+    /// use hql::html::{ElementOrTextRef, Html};
+    ///
+    /// let doc = Html::parse_fragment(r#"<a HREF="/next">next</a>"#, false);
+    /// let Some(ElementOrTextRef::Element(a)) = doc.select_first("@path(`//a`)").unwrap() else {
+    ///     panic!("no link");
+    /// };
+    /// assert_eq!(a.attr_str("href"), Some("/next"));
+    /// assert_eq!(a.attr_str("Href"), Some("/next"));
+    /// assert_eq!(a.attr_str("title"), None);
+    /// ```
+    pub fn attr_str(&self, name: &str) -> Option<&str> {
+        self.get_attr(&QualName::new(None, ns!(), LocalName::from(name)))
+            .map(|v| v.as_ref())
+    }
+
+    /// Original markup of the element, when the document is parsed with
+    /// `HtmlParseOptions::track_raw_source`
+    pub fn raw_source(&self) -> Option<&'a str> {
+        self.element()?.raw_source()
+    }
+
+    /// Byte range of `raw_source` in the parsed input
+    pub fn source_range(&self) -> Option<Range<usize>> {
+        self.element()?.source_range()
+    }
+
+    pub fn attrs(&self) -> impl Iterator<Item = (&QualName, &StrTendril)> {
+        self.element().into_iter().flat_map(Element::attrs)
     }
 
     pub fn has_class(&self, class: &str, case_sensitive: bool) -> bool {
-        self.node
-            .data
-            .as_element()
-            .unwrap()
-            .has_class(class, case_sensitive)
+        self.element()
+            .is_some_and(|e| e.has_class(class, case_sensitive))
     }
 
     pub fn has_id(&self, id: &str, case_sensitive: bool) -> bool {
-        self.node
-            .data
-            .as_element()
-            .unwrap()
-            .id()
-            .map(|i| match case_sensitive {
+        self.element()
+            .and_then(Element::id)
+            .is_some_and(|i| match case_sensitive {
                 true => i == id,
                 false => i.eq_ignore_ascii_case(id),
             })
-            .is_some()
     }
 
     // For element, traverse the whole subtree and extract its text
     pub fn text(&self) -> impl Iterator<Item = &Text> {
-        PreOrderTraverse::new(self.tree, self.node).filter_map(|(n, _)| match &n.data {
-            DomNode::Text(t) => Some(t),
-            _ => None,
-        })
+        PreOrderTraverse::new_pruned(self.tree, self.node, is_template_contents).filter_map(
+            |(n, _)| match &n.data {
+                DomNode::Text(t) => Some(t),
+                _ => None,
+            },
+        )
+    }
+
+    /// Traverse the whole subtree and yield its processing instructions. The HTML parser turns
+    /// `<?target data?>` into comments, so they only exist in trees built otherwise, like by
+    /// `HtmlBuilder`.
+    pub fn processing_instructions(&self) -> impl Iterator<Item = &ProcessingInstruction> {
+        PreOrderTraverse::new_pruned(self.tree, self.node, is_template_contents)
+            .filter_map(|(n, _)| n.data.as_processing_instruction())
+    }
+
+    /// Concatenate the whole subtree text like `text`.
+    ///
+    /// The text is computed on the first call and cached in the element, so repeated calls, like
+    /// running many `#text()` queries on the same document, are cheap. It is safe since the tree
+    /// never changes after parsing.
+    pub fn text_content(&self) -> StrTendril {
+        match self.element() {
+            Some(e) => e
+                .text_or_init(|| self.text().map(|t| t.text()).collect())
+                .clone(),
+            // the document root has no cache
+            None => self.text().map(|t| t.text()).collect(),
+        }
+    }
+
+    /// Number of element children, skipping text and comment children.
+    ///
+    /// Like `text_content`, it is counted on the first call and cached in the element, so selectors
+    /// filtering on it, like `@minChildren()`, do not iterate children again for the same element.
+    pub fn child_element_count(&self) -> usize {
+        let count = || {
+            self.clone()
+                .children(false)
+                .filter(|c| matches!(c, ElementOrTextRef::Element(_)))
+                .count()
+        };
+        match self.element() {
+            Some(e) => e.child_elements_or_init(count),
+            // the document root has no cache
+            None => count(),
+        }
+    }
+
+    /// Like `text`, but concatenate the text with `\n` inserted wherever a `<br>` or `<hr>` element appears
+    pub fn text_with_breaks(&self) -> String {
+        PreOrderTraverse::new_pruned(self.tree, self.node, is_template_contents).fold(
+            String::new(),
+            |mut s, (n, _)| {
+                match &n.data {
+                    DomNode::Text(t) => s.push_str(t.text()),
+                    DomNode::Element(e)
+                        if matches!(
+                            e.name().expanded(),
+                            expanded_name!(html "br") | expanded_name!(html "hr")
+                        ) =>
+                    {
+                        s.push('\n')
+                    }
+                    _ => {}
+                }
+                s
+            },
+        )
+    }
+
+    /// Like `text`, but laid out block by block. A block is the text within a block-level element
+    /// like `<p>` or `<li>`, and `<br>` or `<hr>` elements start a new block too. Inside a block,
+    /// text nodes are concatenated and each run of whitespace becomes sep, trimmed at the edges.
+    /// Blocks are joined with block_sep, and blank blocks are dropped. So
+    /// `<p>Hello <b>world</b>,\n again</p><p>Bye</p>` with `" "` and `"\n\n"` gives
+    /// `Hello world, again\n\nBye`.
+    pub fn text_blocks(&self, sep: &str, block_sep: &str) -> String {
+        // nearest block-level ancestor of a text node inside the subtree, or the element itself
+        let block_of = |n: &'a Node<DomNode>| {
+            std::iter::successors(self.tree.parent_ref(n.id), |p| self.tree.parent_ref(p.id))
+                .find(|p| p.id == self.node.id || is_block(p))
+                .map(|p| p.id)
+        };
+        let flush = |text: &mut String, pending: &mut String| {
+            let mut words = pending.split(is_html_whitespace).filter(|w| !w.is_empty());
+            if let Some(first) = words.next() {
+                if !text.is_empty() {
+                    text.push_str(block_sep);
+                }
+                text.push_str(first);
+                for w in words {
+                    text.push_str(sep);
+                    text.push_str(w);
+                }
+            }
+            pending.clear();
+        };
+
+        let mut text = String::new();
+        // raw text of the current block
+        let mut pending = String::new();
+        let mut block = None;
+        for (n, _) in PreOrderTraverse::new_pruned(self.tree, self.node, is_template_contents) {
+            match &n.data {
+                DomNode::Text(t) => {
+                    let current = block_of(n);
+                    if block != current {
+                        flush(&mut text, &mut pending);
+                        block = current;
+                    }
+                    pending.push_str(t.text());
+                }
+                DomNode::Element(e)
+                    if matches!(
+                        e.name().expanded(),
+                        expanded_name!(html "br") | expanded_name!(html "hr")
+                    ) =>
+                {
+                    flush(&mut text, &mut pending)
+                }
+                _ => {}
+            }
+        }
+        flush(&mut text, &mut pending);
+        text
+    }
+
+    /// Return children of the template contents if the element is a `<template>`, otherwise nothing
+    pub fn template_contents(self) -> impl Iterator<Item = ElementOrTextRef<'a>> {
+        ChildrenTraverse::new(self.tree, self.node, false)
+            .filter(|(n, _)| n.data.is_fragment())
+            .flat_map(|(n, t)| ChildrenTraverse::new(t, n, false))
+            .filter_map(|(n, t)| match n.data {
+                DomNode::Element(_) => {
+                    Some(ElementOrTextRef::Element(ElementRef { tree: t, node: n }))
+                }
+                DomNode::Text(_) => Some(ElementOrTextRef::Text(TextRef { tree: t, node: n })),
+                _ => None,
+            })
     }
 
     pub fn children(self, reversed: bool) -> impl Iterator<Item = ElementOrTextRef<'a>> {
-        ChildrenTraverse::new(self.tree, self.node, reversed).filter_map(|(n, t)| match n.data {
-            DomNode::Element(_) => Some(ElementOrTextRef::Element(ElementRef { tree: t, node: n })),
-            DomNode::Text(_) => Some(ElementOrTextRef::Text(TextRef { tree: t, node: n })),
-            _ => None,
-        })
+        ChildrenTraverse::new(self.tree, self.node, reversed).filter_map(element_or_text)
+    }
+
+    /// Ancestor elements of the element, from its parent upwards. It stops at the document root, and
+    /// at the boundary of template contents.
+    pub fn ancestors(&self) -> impl Iterator<Item = ElementRef<'a>> {
+        let tree = self.tree;
+        std::iter::successors(tree.parent_ref(self.node.id), |n| tree.parent_ref(n.id))
+            .map_while(move |node| node.data.is_element().then_some(ElementRef { tree, node }))
+    }
+
+    /// Element and text siblings following the element, in document order
+    pub fn next_siblings(&self) -> impl Iterator<Item = ElementOrTextRef<'a>> {
+        ChildrenTraverse::new_siblings(self.tree, self.node, false).filter_map(element_or_text)
+    }
+
+    /// Element and text siblings preceding the element, from the nearest one backwards
+    pub fn prev_siblings(&self) -> impl Iterator<Item = ElementOrTextRef<'a>> {
+        ChildrenTraverse::new_siblings(self.tree, self.node, true).filter_map(element_or_text)
+    }
+}
+
+/// Append copies of node subtrees to target. The document root is copied by its children.
+fn append_copies<'a: 'b, 'b>(
+    tree: &mut Tree<DomNode>,
+    target: NodeID,
+    nodes: impl IntoIterator<Item = &'b ElementOrTextRef<'a>>,
+) {
+    for n in nodes {
+        match n {
+            ElementOrTextRef::Element(ElementRef { tree: t, node }) if !node.data.is_element() => {
+                for (child, _) in ChildrenTraverse::new(t, node, false) {
+                    tree.append_subtree_clone(target, t, child);
+                }
+            }
+            ElementOrTextRef::Element(ElementRef { tree: t, node })
+            | ElementOrTextRef::Text(TextRef { tree: t, node }) => {
+                tree.append_subtree_clone(target, t, node);
+            }
+            ElementOrTextRef::PhantomText(t) => {
+                tree.append_child(target, t.text.data.clone());
+            }
+            ElementOrTextRef::PhantomElement(e) => {
+                tree.append_subtree_clone(target, &e.tree, e.tree.root_ref().unwrap());
+            }
+        }
+    }
+}
+
+/// Wrap Element and Text nodes, dropping others like comments
+fn element_or_text<'a>(
+    (node, tree): (&'a Node<DomNode>, &'a Tree<DomNode>),
+) -> Option<ElementOrTextRef<'a>> {
+    match node.data {
+        DomNode::Element(_) => Some(ElementOrTextRef::Element(ElementRef { tree, node })),
+        DomNode::Text(_) => Some(ElementOrTextRef::Text(TextRef { tree, node })),
+        _ => None,
     }
 }
 
@@ -173,8 +894,12 @@ pub struct TextRef<'a> {
 }
 
 impl<'a> TextRef<'a> {
-    pub fn text(&self) -> &Text {
-        self.node.data.as_text().unwrap()
+    pub fn text(&self) -> &'a Text {
+        // TextRef is only built from text nodes
+        self.node
+            .data
+            .as_text()
+            .expect("TextRef points to a non-text node")
     }
 }
 
@@ -212,7 +937,11 @@ impl PhantomTextRef {
     }
 
     pub fn text(&self) -> &Text {
-        self.text.data.as_text().unwrap()
+        // all constructors wrap a text node
+        self.text
+            .data
+            .as_text()
+            .expect("PhantomTextRef wraps a non-text node")
     }
 }
 
@@ -222,11 +951,50 @@ impl Display for PhantomTextRef {
     }
 }
 
+/// PhantomElementRef is an element built during querying, like by `@wrap()`. It owns a copy of
+/// its subtree, so it is detached from the queried document.
+#[derive(Debug, Clone)]
+pub struct PhantomElementRef {
+    tree: Rc<Tree<DomNode>>,
+}
+
+impl PhantomElementRef {
+    /// Build an element `name` without attributes, whose children are copies of nodes
+    pub fn wrap<'a: 'b, 'b>(
+        name: QualName,
+        nodes: impl IntoIterator<Item = &'b ElementOrTextRef<'a>>,
+    ) -> Self {
+        let mut tree = Tree::new(DomNode::Element(dom::Element::new(name, vec![])));
+        let root = tree.root_ref().unwrap().id;
+        append_copies(&mut tree, root, nodes);
+
+        Self {
+            tree: Rc::new(tree),
+        }
+    }
+
+    pub fn element(&self) -> ElementRef<'_> {
+        ElementRef {
+            tree: &self.tree,
+            node: self.tree.root_ref().unwrap(),
+        }
+    }
+}
+
+impl Display for PhantomElementRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.element())
+    }
+}
+
+/// Node in query results. Phantom nodes are built during querying and are not part of the document,
+/// so selectors navigating the document, like `@path()`, yield nothing from them.
 #[derive(Debug, Clone)]
 pub enum ElementOrTextRef<'a> {
     Element(ElementRef<'a>),
     Text(TextRef<'a>),
     PhantomText(PhantomTextRef),
+    PhantomElement(PhantomElementRef),
 }
 
 impl<'a> Display for ElementOrTextRef<'a> {
@@ -235,15 +1003,27 @@ impl<'a> Display for ElementOrTextRef<'a> {
             ElementOrTextRef::Element(e) => write!(f, "{}", e),
             ElementOrTextRef::Text(t) => write!(f, "{}", t),
             ElementOrTextRef::PhantomText(t) => write!(f, "{}", t),
+            ElementOrTextRef::PhantomElement(e) => write!(f, "{}", e),
         }
     }
 }
 impl<'a> ElementOrTextRef<'a> {
+    /// Original markup of elements, when the document is parsed with
+    /// `HtmlParseOptions::track_raw_source`. Text nodes and elements built during querying, like
+    /// by `@wrap()`, have none.
+    pub fn raw_source(&self) -> Option<&str> {
+        match self {
+            ElementOrTextRef::Element(e) => e.raw_source(),
+            ElementOrTextRef::PhantomElement(e) => e.element().raw_source(),
+            ElementOrTextRef::Text(_) | ElementOrTextRef::PhantomText(_) => None,
+        }
+    }
+
     fn into_children(self, reversed: bool) -> Option<ChildrenTraverse<'a, DomNode>> {
         match self {
             ElementOrTextRef::Element(e) => Some(ChildrenTraverse::new(e.tree, e.node, reversed)),
             ElementOrTextRef::Text(t) => Some(ChildrenTraverse::new(t.tree, t.node, reversed)),
-            ElementOrTextRef::PhantomText(_) => None,
+            ElementOrTextRef::PhantomText(_) | ElementOrTextRef::PhantomElement(_) => None,
         }
     }
 
@@ -256,22 +1036,103 @@ impl<'a> ElementOrTextRef<'a> {
     }
 }
 
+/// Concatenate `as_text_cow` of all nodes
+impl<'a> FromIterator<ElementOrTextRef<'a>> for String {
+    fn from_iter<I: IntoIterator<Item = ElementOrTextRef<'a>>>(iter: I) -> Self {
+        iter.into_iter().fold(String::new(), |mut s, n| {
+            s.push_str(&n.as_text_cow());
+            s
+        })
+    }
+}
+
 impl<'a> From<ElementOrTextRef<'a>> for Option<PreOrderTraverse<'a, DomNode>> {
     fn from(val: ElementOrTextRef<'a>) -> Self {
         match val {
-            ElementOrTextRef::Element(e) => Some(PreOrderTraverse::new(e.tree, e.node)),
-            ElementOrTextRef::Text(t) => Some(PreOrderTraverse::new(t.tree, t.node)),
-            ElementOrTextRef::PhantomText(_) => None,
+            ElementOrTextRef::Element(e) => Some(PreOrderTraverse::new_pruned(
+                e.tree,
+                e.node,
+                is_template_contents,
+            )),
+            ElementOrTextRef::Text(t) => Some(PreOrderTraverse::new_pruned(
+                t.tree,
+                t.node,
+                is_template_contents,
+            )),
+            ElementOrTextRef::PhantomText(_) | ElementOrTextRef::PhantomElement(_) => None,
         }
     }
 }
 
 impl<'a> ElementOrTextRef<'a> {
+    /// Return the text for Text and PhantomText nodes, and the serialized HTML for Element and
+    /// PhantomElement nodes
+    pub fn as_text_cow(&self) -> Cow<'_, str> {
+        match self {
+            ElementOrTextRef::Element(e) => Cow::Owned(e.html()),
+            ElementOrTextRef::Text(t) => Cow::Borrowed(t.text().text()),
+            ElementOrTextRef::PhantomText(t) => Cow::Borrowed(t.text().text()),
+            ElementOrTextRef::PhantomElement(e) => Cow::Owned(e.element().html()),
+        }
+    }
+
+    /// Return id of the node, which can be used to sort nodes in document order. Phantom nodes share
+    /// the sentinel `NodeID::from(usize::MAX)` so that they are placed after all real nodes.
+    ///
+    /// Ids are assigned in parse order, which equals document order except for nodes moved by the
+    /// html5ever tree builder, like foster-parented table content.
+    pub fn document_position(&self) -> NodeID {
+        match self {
+            ElementOrTextRef::PhantomElement(_) => NodeID::from(usize::MAX),
+            _ => self.node().id,
+        }
+    }
+
+    /// Return the root of the document the node belongs to, like `Html::root`. Phantom nodes are not
+    /// part of the document, so None is returned for them.
+    pub fn document_root(&self) -> Option<ElementRef<'a>> {
+        let tree = match self {
+            ElementOrTextRef::Element(e) => e.tree,
+            ElementOrTextRef::Text(t) => t.tree,
+            ElementOrTextRef::PhantomText(_) | ElementOrTextRef::PhantomElement(_) => return None,
+        };
+        tree.root_ref().map(|node| ElementRef { tree, node })
+    }
+
+    /// Return the parent element of the node. Top-level nodes, whose parent is the document root,
+    /// and phantom nodes have no parent element.
+    pub fn parent(&self) -> Option<ElementRef<'a>> {
+        let (tree, node) = match self {
+            ElementOrTextRef::Element(e) => (e.tree, e.node),
+            ElementOrTextRef::Text(t) => (t.tree, t.node),
+            ElementOrTextRef::PhantomText(_) | ElementOrTextRef::PhantomElement(_) => return None,
+        };
+        tree.parent_ref(node.id)
+            .filter(|p| p.data.is_element())
+            .map(|node| ElementRef { tree, node })
+    }
+
+    /// Return the number of ancestor elements of the node, like `@depth()`. The root element `<html>`
+    /// has depth 0, and so do phantom nodes, which have no ancestors.
+    pub fn depth(&self) -> usize {
+        self.parent().map_or(0, |p| p.ancestors().count() + 1)
+    }
+
+    pub fn kind(&self) -> ResultKind {
+        match self {
+            ElementOrTextRef::Element(_) => ResultKind::Element,
+            ElementOrTextRef::Text(_) => ResultKind::Text,
+            ElementOrTextRef::PhantomText(_) => ResultKind::Phantom,
+            ElementOrTextRef::PhantomElement(_) => ResultKind::PhantomElement,
+        }
+    }
+
     pub fn node(&self) -> &Node<DomNode> {
         match self {
             ElementOrTextRef::Element(e) => e.node,
             ElementOrTextRef::Text(t) => t.node,
             ElementOrTextRef::PhantomText(t) => &t.text,
+            ElementOrTextRef::PhantomElement(e) => e.tree.root_ref().unwrap(),
         }
     }
 
@@ -312,25 +1173,674 @@ impl<'a> ElementOrTextRef<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResultKind {
+    Element,
+    Text,
+    Phantom,
+    PhantomElement,
+}
+
+/// QueryResult is an owned snapshot of an `ElementOrTextRef`, free of the document lifetime.
+///
+/// For elements, `text` is the concatenation of the whole subtree text.
+/// For text and phantom text nodes, `tag` is None and `attributes` is empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    kind: ResultKind,
+    tag: Option<String>,
+    attributes: HashMap<String, String>,
+    text: String,
+}
+
+impl QueryResult {
+    pub fn kind(&self) -> ResultKind {
+        self.kind
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn attributes(&self) -> &HashMap<String, String> {
+        &self.attributes
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|v| v.as_str())
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl<'a> From<&ElementOrTextRef<'a>> for QueryResult {
+    fn from(val: &ElementOrTextRef<'a>) -> Self {
+        let from_element = |kind, e: &ElementRef| Self {
+            kind,
+            tag: e.name().map(|n| n.local.to_string()),
+            attributes: e
+                .attrs()
+                .map(|(k, v)| (k.local.to_string(), v.to_string()))
+                .collect(),
+            text: e.text().map(|t| t.text().as_ref()).collect(),
+        };
+
+        match val {
+            ElementOrTextRef::Element(e) => from_element(ResultKind::Element, e),
+            ElementOrTextRef::Text(t) => Self {
+                kind: ResultKind::Text,
+                tag: None,
+                attributes: HashMap::new(),
+                text: t.text().text().to_string(),
+            },
+            ElementOrTextRef::PhantomText(t) => Self {
+                kind: ResultKind::Phantom,
+                tag: None,
+                attributes: HashMap::new(),
+                text: t.text().text().to_string(),
+            },
+            ElementOrTextRef::PhantomElement(e) => {
+                from_element(ResultKind::PhantomElement, &e.element())
+            }
+        }
+    }
+}
+
+impl<'a> From<ElementOrTextRef<'a>> for QueryResult {
+    fn from(val: ElementOrTextRef<'a>) -> Self {
+        (&val).into()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::fs;
 
-    use tracing::level_filters::LevelFilter;
+    use html5ever::QualName;
 
-    use super::Html;
+    use crate::querier::Querier;
+
+    use super::{
+        dom::{DomNode, Element},
+        ElementOrTextRef, Html, HtmlBuilder, HtmlParseOptions, HtmlSerializeOptions, NodeID,
+        QueryResult, ResultKind,
+    };
 
     #[test]
-    fn test_parse_document() {
-        tracing_subscriber::fmt::fmt()
-            .with_env_filter(
-                tracing_subscriber::EnvFilter::builder()
-                    .with_default_directive(LevelFilter::INFO.into())
-                    .from_env_lossy(),
+    fn test_serialize_element() {
+        let dom = Html::parse_fragment(
+            r#"<div id="a" class="b"><img src="x">a &amp; b<!--c--><br></div>"#,
+            false,
+        );
+
+        let div = match dom.root().traverse_subtree().nth(1).unwrap() {
+            super::ElementOrTextRef::Element(e) => e,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            div.html(),
+            r#"<div class="b" id="a"><img src="x">a &amp; b<!--c--><br></div>"#
+        );
+        assert_eq!(div.inner_html(), r#"<img src="x">a &amp; b<!--c--><br>"#);
+    }
+
+    #[test]
+    fn test_serialize_escaped_text() {
+        let dom = Html::parse_fragment("<p>a &lt; b &amp;&amp; c &gt; d</p>", false);
+        let text = Querier::try_parse("@path(`//p`) | @child(0)")
+            .unwrap()
+            .query_document(&dom)
+            .remove(0);
+
+        // Display writes the raw text, without the leading space it used to have
+        assert_eq!(text.to_string(), "a < b && c > d");
+        assert_eq!(text.to_html(), "a &lt; b &amp;&amp; c &gt; d");
+        assert_eq!(
+            dom.html(),
+            "<html><p>a &lt; b &amp;&amp; c &gt; d</p></html>"
+        );
+
+        let doc = Html::parse_document("<!DOCTYPE html><title>1 < 2</title><p>&amp;", false);
+        assert_eq!(
+            doc.html(),
+            "<!DOCTYPE html><html><head><title>1 &lt; 2</title></head><body><p>&amp;</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_serialize_collapse_whitespace() {
+        let dom = Html::parse_fragment(
+            "<div>\n  <p>a  b</p>  \n\n  <p> </p><pre>  <b> </b>\n</pre>\t<textarea>  x\n</textarea> </div>",
+            false,
+        );
+        let div = match dom.root().traverse_subtree().nth(1).unwrap() {
+            ElementOrTextRef::Element(e) => e,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(div.html_with(&HtmlSerializeOptions::new()), div.html());
+        assert_eq!(
+            div.html(),
+            "<div>\n  <p>a  b</p>  \n\n  <p> </p><pre>  <b> </b>\n</pre>\t<textarea>  x\n</textarea> </div>"
+        );
+        assert_eq!(
+            div.html_with(&HtmlSerializeOptions::new().collapse_whitespace(true)),
+            "<div>\n<p>a  b</p>\n<p> </p><pre>  <b> </b>\n</pre> <textarea>  x\n</textarea> </div>"
+        );
+
+        // whitespace inside a preserving ancestor is kept
+        let b = match ElementOrTextRef::Element(div)
+            .traverse_subtree()
+            .find(|n| n.to_html() == "<b> </b>")
+        {
+            Some(ElementOrTextRef::Element(e)) => e,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            b.html_with(&HtmlSerializeOptions::new().collapse_whitespace(true)),
+            "<b> </b>"
+        );
+    }
+
+    #[test]
+    fn test_parse_fragment_with_context() {
+        let frag = "<td>x</td><td>y</td>";
+        let q = Querier::try_parse("@path(`//td`) | #text()").unwrap();
+
+        let doc = Html::parse_fragment_with_context(
+            frag,
+            QualName::new(None, ns!(html), local_name!("tr")),
+            false,
+        );
+        assert_eq!(
+            Querier::results_to_string(&q.query_document(&doc), ","),
+            "x,y"
+        );
+
+        // cells are dropped in body, leaving their text
+        let doc = Html::parse_fragment(frag, false);
+        assert!(q.query_document(&doc).is_empty());
+        assert_eq!(doc.root().as_text_cow(), "<html>xy</html>");
+    }
+
+    #[test]
+    fn test_node_ref_by_id() {
+        // ids follow the build order: 0 root, 1 ul, 2 li, 3 "a", 4 li, 5 "b", 6 pi
+        let doc = Html::builder()
+            .element("ul")
+            .child(HtmlBuilder::new().element("li").text("a"))
+            .child(HtmlBuilder::new().element("li").text("b"))
+            .processing_instruction("x", "y")
+            .build();
+        let q = Querier::try_parse("@children() | #text()").unwrap();
+
+        let ul = doc.node_ref_by_id(NodeID::from(1)).unwrap();
+        assert_eq!(ul.document_position(), NodeID::from(1));
+        assert_eq!(Querier::results_to_string(&q.query_node(ul), ","), "a,b");
+        let text = doc.node_ref_by_id(NodeID::from(5)).unwrap();
+        assert_eq!(text.kind(), ResultKind::Text);
+        assert_eq!(text.as_text_cow(), "b");
+        assert_eq!(
+            doc.node_ref_by_id(NodeID::from(0))
+                .unwrap()
+                .document_position(),
+            doc.root().document_position()
+        );
+
+        assert!(doc.node_ref_by_id(NodeID::from(6)).is_none());
+        assert!(doc.node_ref_by_id(NodeID::from(7)).is_none());
+
+        // dropped whitespace is detached
+        let doc = Html::parse_fragment_with(
+            "<p>a</p> <p>b</p>",
+            &HtmlParseOptions::new().drop_whitespace(true),
+        );
+        let ids = std::iter::once(doc.root())
+            .chain(doc.root().traverse_subtree())
+            .map(|n| n.document_position())
+            .collect::<Vec<_>>();
+        let all = (0..20)
+            .map(NodeID::from)
+            .filter(|id| doc.node_ref_by_id(*id).is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(all, ids);
+    }
+
+    #[test]
+    fn test_replace_node_data() {
+        let mut doc = Html::parse_fragment("<ul><li class=x>a</li><li>b</li></ul>", false);
+        let id = doc
+            .select_first("@path(`//li`)")
+            .unwrap()
+            .unwrap()
+            .document_position();
+        let positions = |doc: &Html| {
+            doc.root()
+                .traverse_subtree()
+                .map(|n| n.document_position())
+                .collect::<Vec<_>>()
+        };
+        let before = positions(&doc);
+
+        let dt = Element::new(QualName::new(None, ns!(html), local_name!("dt")), vec![]);
+        let old = doc.replace_node_data(id, DomNode::Element(dt)).unwrap();
+        assert_eq!(old.as_element().unwrap().name().local, local_name!("li"));
+        assert_eq!(doc.html(), "<html><ul><dt>a</dt><li>b</li></ul></html>");
+        assert_eq!(positions(&doc), before);
+        assert_eq!(
+            doc.select_first("@path(`//dt`)")
+                .unwrap()
+                .unwrap()
+                .document_position(),
+            id
+        );
+
+        assert!(doc
+            .replace_node_data(NodeID::from(100), DomNode::Fragment)
+            .is_none());
+    }
+
+    #[test]
+    fn test_serialize_deep_nested() {
+        let depth = 10_000;
+        let s = format!("{}x{}", "<div>".repeat(depth), "</div>".repeat(depth));
+        let dom = Html::parse_fragment(&s, false);
+
+        let html = match dom.root().traverse_subtree().next().unwrap() {
+            super::ElementOrTextRef::Element(e) => e,
+            _ => unreachable!(),
+        };
+        let out = html.html();
+        assert_eq!(out.matches("<div>").count(), depth);
+        assert_eq!(out.matches("</div>").count(), depth);
+        assert!(out.contains("<div>x</div>"));
+
+        assert_eq!(html.try_html(depth + 1).unwrap(), out);
+        assert_eq!(
+            html.try_html(100).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_siblings() {
+        let doc = Html::parse_fragment(
+            "<ul><li>1</li><li>2</li>text<!-- comment --><li>3</li><li>4</li></ul>",
+            false,
+        );
+        let li = |n: usize| match doc
+            .root()
+            .traverse_subtree()
+            .filter(
+                |n| matches!(n, ElementOrTextRef::Element(e) if e.expanded_name().local == "li"),
             )
-            .init();
+            .nth(n)
+        {
+            Some(ElementOrTextRef::Element(e)) => e,
+            _ => unreachable!(),
+        };
+        let texts = |nodes: Vec<ElementOrTextRef>| {
+            nodes
+                .iter()
+                .map(|n| QueryResult::from(n).text().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts(li(1).next_siblings().collect()),
+            vec!["text", "3", "4"]
+        );
+        assert_eq!(texts(li(1).prev_siblings().collect()), vec!["1"]);
+        assert!(li(0).prev_siblings().next().is_none());
+        assert!(li(3).next_siblings().next().is_none());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let doc = "<html><body><noscript><p>hi</p></noscript><p>x</body></html>";
+
+        let default = Html::parse_document(doc, false);
+        let q = crate::querier::Querier::try_parse("@path(`//p`)").unwrap();
+        assert_eq!(q.query_document(&default).len(), 1);
+
+        let opts = HtmlParseOptions::new()
+            .exact_errors(true)
+            .scripting_enabled(false);
+        let html = Html::parse_document_with(doc, &opts);
+        assert_eq!(q.query_document(&html).len(), 2);
+        assert!(!html.errors().is_empty());
+    }
+
+    #[test]
+    fn test_raw_source() {
+        let doc = concat!(
+            "<!DOCTYPE html><html><body>\n",
+            "<div class=a  id='main' data-x=\"1>2<3\"><P>one &amp; two<br>\n",
+            "<p>three</div><script>if (a<b) x = \"</p>\";</script>\n",
+            "<table><tr><td>cell</td></tr></table><i>x<!-- a > b --></i><b>1<p>2</b>3</p>\n",
+            "<svg><clipPath/></svg></body></html>\n",
+        );
+        let opts = HtmlParseOptions::new().track_raw_source(true);
+        let html = Html::parse_document_with(doc, &opts);
+        let raw = |hql| raw_of(&html, hql);
+
+        assert_eq!(
+            raw("@path(`//div`)"),
+            vec![Some(
+                "<div class=a  id='main' data-x=\"1>2<3\"><P>one &amp; two<br>\n<p>three</div>"
+                    .into()
+            )]
+        );
+        // the first `<p>` is closed by the second one, which is closed by `</div>`
+        assert_eq!(
+            raw("@path(`//p`)"),
+            vec![
+                Some("<P>one &amp; two<br>\n".into()),
+                Some("<p>three".into()),
+                Some("<p>2</b>3</p>".into())
+            ]
+        );
+        assert_eq!(raw("@path(`//br`)"), vec![Some("<br>".into())]);
+        assert_eq!(
+            raw("@path(`//script`)"),
+            vec![Some("<script>if (a<b) x = \"</p>\";</script>".into())]
+        );
+        assert_eq!(raw("@path(`//td`)"), vec![Some("<td>cell</td>".into())]);
+        assert_eq!(
+            raw("@path(`//i`)"),
+            vec![Some("<i>x<!-- a > b --></i>".into())]
+        );
+        // `</b>` is misnested in `<p>`, so the parser moves `<p>` out and opens another `<b>` in it
+        assert_eq!(raw("@path(`//b`)"), vec![Some("<b>1".into()), None]);
+        assert_eq!(raw("@path(`//clipPath`)"), vec![Some("<clipPath/>".into())]);
+        // implied by the parser, so there is no markup
+        assert_eq!(raw("@path(`//tbody`)"), vec![None]);
+        assert_eq!(raw("@path(`//td`) | @child(0)"), vec![None]);
+
+        let div = html.select_first("@path(`//div`)").unwrap().unwrap();
+        let ElementOrTextRef::Element(div) = div else {
+            unreachable!()
+        };
+        assert_eq!(&doc[div.source_range().unwrap()], div.raw_source().unwrap());
+
+        // fragments, and not tracked by default
+        let frag = Html::parse_fragment_with("a<b title=\"&lt;\">b</b>c", &opts);
+        assert_eq!(
+            raw_of(&frag, "@path(`//b`)"),
+            vec![Some("<b title=\"&lt;\">b</b>".into())]
+        );
+        let html = Html::parse_document(doc, false);
+        assert_eq!(raw_of(&html, "@path(`//div`)"), vec![None]);
+    }
+
+    fn raw_of(html: &Html, hql: &str) -> Vec<Option<String>> {
+        html.select(hql)
+            .unwrap()
+            .iter()
+            .map(|n| n.raw_source().map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn test_drop_whitespace() {
+        let doc = r#"<html>
+  <head>
+    <title> </title>
+  </head>
+  <body>
+    <ul>
+      <li>a <b>b</b></li>
+      <li>&nbsp;</li>
+    </ul>
+    <pre>
+  <b> </b>
+</pre>
+  </body>
+</html>"#;
+        let texts = |html: &Html| {
+            html.root()
+                .traverse_subtree()
+                .filter_map(|n| match n {
+                    ElementOrTextRef::Text(t) => Some(t.text().text().to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let kept = Html::parse_document(doc, false);
+        let dropped =
+            Html::parse_document_with(doc, &HtmlParseOptions::new().drop_whitespace(true));
+        assert_eq!(
+            kept.root().traverse_subtree().count() - dropped.root().traverse_subtree().count(),
+            9
+        );
+        assert_eq!(
+            texts(&dropped),
+            vec![" ", "a ", "b", "\u{a0}", "  ", " ", "\n"]
+        );
+    }
+
+    #[test]
+    fn test_text_content() {
+        let dom = Html::parse_fragment("<div>a<p>b<b>c</b></p><template>t</template></div>", false);
+        let div = match dom.root().traverse_subtree().nth(1).unwrap() {
+            ElementOrTextRef::Element(e) => e,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(&*div.text_content(), "abc");
+        // the cached text is returned afterwards
+        assert_eq!(&*div.text_content(), "abc");
+    }
+
+    #[test]
+    fn test_child_element_count() {
+        let dom = Html::parse_fragment("<ul>a<li></li><!-- c --><li><b></b></li></ul>", false);
+        let ul = match dom.root().traverse_subtree().nth(1).unwrap() {
+            ElementOrTextRef::Element(e) => e,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(ul.child_element_count(), 2);
+        // the cached count is returned afterwards
+        assert_eq!(ul.child_element_count(), 2);
+        match dom.root() {
+            ElementOrTextRef::Element(root) => assert_eq!(root.child_element_count(), 1),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_to_html() {
+        let dom = Html::parse_fragment(
+            r#"<p title='"x" &amp; y'>a &amp; b &lt;c&gt;<img src="x"></p>"#,
+            false,
+        );
+
+        let to_html = |hql: &str| {
+            Querier::try_parse(hql)
+                .unwrap()
+                .query_document(&dom)
+                .iter()
+                .map(|n| n.to_html())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(to_html("@path(`//img`)"), vec![r#"<img src="x">"#]);
+        assert_eq!(
+            to_html("@path(`//p`)"),
+            vec![r#"<p title="&quot;x&quot; &amp; y">a &amp; b &lt;c&gt;<img src="x"></p>"#]
+        );
+        assert_eq!(
+            to_html("@path(`//p`) | #text()"),
+            vec!["a &amp; b &lt;c&gt;"]
+        );
+        assert_eq!(
+            to_html("@path(`//p`) | #attr(`title`)"),
+            vec!["\"x\" &amp; y"]
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let doc = Html::parse_fragment("<p>a</p><p>b</p>", false);
+
+        let texts = doc.select("@path(`//p`) | #text()").unwrap();
+        assert_eq!(Querier::results_to_string(&texts, ","), "a,b");
+
+        let first = doc.select_first("@path(`//p`) | #text()").unwrap();
+        assert_eq!(first.unwrap().as_text_cow(), "a");
+        assert!(doc.select_first("@path(`//a`)").unwrap().is_none());
+        assert!(doc.select_first("#text(").is_err());
+    }
+
+    #[test]
+    fn test_root_element_methods() {
+        let dom = Html::parse_fragment(r#"<p id="x" class="x">a</p>b"#, false);
+        let root = match dom.root() {
+            ElementOrTextRef::Element(e) => e,
+            _ => unreachable!(),
+        };
+
+        // the document root is wrapped as an element without name or attributes
+        assert!(root.name().is_none());
+        assert!(!root.is_tag("p"));
+        assert_eq!(root.attrs().count(), 0);
+        assert!(!root.has_class("x", true));
+        assert!(!root.has_id("x", true));
+        assert_eq!(&*root.text_content(), "ab");
+        assert_eq!(root.inner_html(), root.html());
+        assert_eq!(QueryResult::from(&dom.root()).tag(), None);
+
+        // element-only selectors drop it instead of panicking
+        for hql in [
+            "@attr(`id`)",
+            "@class(`x`)",
+            "@id(`x`)",
+            "@attrNames()",
+            "#attr(`id`)",
+            "#tagName()",
+        ] {
+            let q = Querier::try_parse(hql).unwrap();
+            assert!(q.query_document(&dom).is_empty(), "{}", hql);
+        }
+    }
+
+    #[test]
+    fn test_dump_tree() {
+        let dom = Html::parse_document(
+            r#"<!DOCTYPE html><html><body><p class="x" id="a">hi<!--c--></p></body></html>"#,
+            false,
+        );
+
+        assert_eq!(
+            dom.dump_tree(),
+            [
+                "0 Document",
+                "  1 DocType html",
+                "  2 <html>",
+                "    3 <head>",
+                "    4 <body>",
+                "      5 <p class=\"x\" id=\"a\">",
+                "        6 Text \"hi\"",
+                "        7 Comment \"c\"",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_foreign_names_case() {
+        let doc = r#"<html><body><svg viewBox="0 0 1 1"><clipPath id="c"></clipPath></svg></body></html>"#;
+        let q = |hql: &str, html: &Html| {
+            Querier::results_to_string(&Querier::try_parse(hql).unwrap().query_document(html), ",")
+        };
+
+        let preserved = Html::parse_document(doc, false);
+        assert_eq!(
+            q("@path(`//clipPath`)", &preserved),
+            r#"<clipPath id="c"></clipPath>"#
+        );
+        assert_eq!(
+            q("@path(`//clippath`)", &preserved),
+            q("@path(`//clipPath`)", &preserved)
+        );
+        assert_eq!(
+            q(
+                "@path(`//svg`) | @attr(`viewBox`) | #attr(`viewBox`)",
+                &preserved
+            ),
+            "0 0 1 1"
+        );
+
+        let lowercased =
+            Html::parse_document_with(doc, &HtmlParseOptions::new().lowercase_foreign_names(true));
+        assert_eq!(
+            q("@path(`//clipPath`)", &lowercased),
+            r#"<clippath id="c"></clippath>"#
+        );
+        assert_eq!(
+            q("@path(`//svg`) | #attr(`viewbox`)", &lowercased),
+            "0 0 1 1"
+        );
+    }
+
+    #[test]
+    fn test_as_text_cow() {
+        let dom = Html::parse_fragment(r#"<p>a <b>b</b></p>"#, false);
+
+        let s: String = dom.root().traverse_subtree().skip(1).collect();
+        assert_eq!(s, "<p>a <b>b</b></p>a <b>b</b>b");
+    }
+
+    #[test]
+    fn test_incremental_parse() {
+        let s = "<html><head><title>标题</title></head><body><p class=\"x\">你好 <b>world</b></p></body></html>";
+        let bytes = s.as_bytes();
+
+        let mut parser = Html::parser(false);
+        // split inside multi-byte characters on purpose
+        parser.process(&bytes[..20]);
+        parser.process(&bytes[20..60]);
+        parser.process(&bytes[60..]);
+        let dom = parser.finish();
+
+        assert_eq!(dom.to_string(), Html::parse_document(s, false).to_string());
+    }
+
+    #[test]
+    fn test_tag_histogram_and_attribute_names() {
+        let dom = Html::parse_document(
+            r#"<html><body><div id="a"><p class="x">1</p><p>2</p></div><a href="/" target="_blank"></a></body></html>"#,
+            false,
+        );
+
+        let histogram = dom.tag_histogram();
+        assert_eq!(histogram.get("p"), Some(&2));
+        assert_eq!(histogram.get("div"), Some(&1));
+        assert_eq!(histogram.get("a"), Some(&1));
+        assert_eq!(histogram.get("html"), Some(&1));
+        assert_eq!(histogram.get("span"), None);
+
+        let names = dom.attribute_names();
+        assert_eq!(
+            names,
+            ["id", "class", "href", "target"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+    }
+
+    #[test]
+    fn test_parse_document() {
+        crate::init_test_tracing();
 
-        let s = fs::read_to_string("./sof.html").unwrap();
+        // the former fixture `./sof.html` is not in the repository, so use a checked-in page
+        let s = fs::read_to_string("../docs/test-html/ytb.html").unwrap();
 
         let dom = Html::parse_document(&s, true);
 
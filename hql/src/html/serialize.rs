@@ -0,0 +1,297 @@
+//! Serialize DOM subtree back into HTML, using [html5ever](https://docs.rs/html5ever) serializer
+//!
+//! The subtree is walked iteratively instead of recursively, so deeply nested documents will not
+//! overflow the stack. Callers handling untrusted input can still limit the depth of serialized
+//! nodes to bound the output, see `ElementRef::try_html`. Attributes are written in the order of
+//! their names to keep output stable. Other options of the output, like collapsing whitespace, are
+//! set by `HtmlSerializeOptions`.
+
+use std::io;
+
+use html5ever::serialize::{
+    serialize, HtmlSerializer, Serialize, SerializeOpts, Serializer, TraversalScope,
+};
+
+use crate::tree::{ChildrenTraverse, Node, Tree};
+
+use super::{
+    dom::DomNode, is_html_whitespace, preserves_whitespace, ElementOrTextRef, ElementRef, Html,
+};
+
+/// Options of serializing HTML, built in builder style from `HtmlSerializeOptions::default()`
+#[derive(Debug, Clone, Default)]
+pub struct HtmlSerializeOptions {
+    collapse_whitespace: bool,
+}
+
+impl HtmlSerializeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write each run of adjacent whitespace-only text nodes, like indentation between block
+    /// elements, as a single newline if the run contains any, or a single space otherwise. Browsers
+    /// collapse such whitespace anyway, so rendering does not change. Subtrees which preserve
+    /// whitespace, like `<pre>` and `<textarea>`, are written verbatim. Default false
+    pub fn collapse_whitespace(mut self, collapse_whitespace: bool) -> Self {
+        self.collapse_whitespace = collapse_whitespace;
+        self
+    }
+}
+
+enum Step<'a> {
+    // node, its depth relative to the serialized root, and whether to collapse whitespace among
+    // its children
+    Open(&'a Node<DomNode>, usize, bool),
+    Close(&'a Node<DomNode>),
+    // a collapsed run of whitespace text
+    Blank(&'static str),
+}
+
+fn push_children<'a>(
+    stack: &mut Vec<Step<'a>>,
+    tree: &'a Tree<DomNode>,
+    node: &'a Node<DomNode>,
+    depth: usize,
+    collapse: bool,
+) {
+    let collapse = collapse && !preserves_whitespace(node);
+    if !collapse {
+        stack.extend(
+            ChildrenTraverse::new(tree, node, true).map(|(n, _)| Step::Open(n, depth, false)),
+        );
+        return;
+    }
+
+    // children are pushed in reverse order, and so are runs of whitespace text
+    let mut blank: Option<&'static str> = None;
+    for (n, _) in ChildrenTraverse::new(tree, node, true) {
+        if let Some(t) = n.data.as_text() {
+            if t.text().is_empty() {
+                continue;
+            }
+            if t.text().trim_matches(is_html_whitespace).is_empty() {
+                blank = match (blank, t.text().contains('\n')) {
+                    (Some("\n"), _) | (_, true) => Some("\n"),
+                    _ => Some(" "),
+                };
+                continue;
+            }
+        }
+        if let Some(b) = blank.take() {
+            stack.push(Step::Blank(b));
+        }
+        stack.push(Step::Open(n, depth, true));
+    }
+    if let Some(b) = blank {
+        stack.push(Step::Blank(b));
+    }
+}
+
+/// Serialize subtree of node. Return an InvalidData error if any node is deeper than max_depth.
+pub(crate) fn serialize_subtree<S: Serializer>(
+    tree: &Tree<DomNode>,
+    node: &Node<DomNode>,
+    serializer: &mut S,
+    traversal_scope: TraversalScope,
+    max_depth: Option<usize>,
+    opts: &HtmlSerializeOptions,
+) -> io::Result<()> {
+    // whitespace is kept inside preserving ancestors of the serialized root
+    let mut ancestors = std::iter::successors(tree.parent_ref(node.id), |n| tree.parent_ref(n.id));
+    let collapse = opts.collapse_whitespace && !ancestors.any(preserves_whitespace);
+
+    let mut stack = vec![];
+    match traversal_scope {
+        TraversalScope::IncludeNode => stack.push(Step::Open(node, 0, collapse)),
+        TraversalScope::ChildrenOnly(_) => push_children(&mut stack, tree, node, 1, collapse),
+    }
+
+    while let Some(step) = stack.pop() {
+        if let Step::Open(_, depth, _) = step {
+            if max_depth.is_some_and(|m| depth > m) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("node depth exceeds the max depth {}", max_depth.unwrap()),
+                ));
+            }
+        }
+
+        match step {
+            Step::Open(n, depth, collapse) => match &n.data {
+                DomNode::Document | DomNode::Fragment => {
+                    push_children(&mut stack, tree, n, depth + 1, collapse)
+                }
+                DomNode::Element(e) => {
+                    let mut attrs = e.attrs().map(|(k, v)| (k, v.as_ref())).collect::<Vec<_>>();
+                    attrs.sort_by(|a, b| a.0.local.cmp(&b.0.local));
+
+                    serializer.start_elem(e.name().clone(), attrs.into_iter())?;
+                    stack.push(Step::Close(n));
+                    push_children(&mut stack, tree, n, depth + 1, collapse);
+                }
+                DomNode::Text(t) => serializer.write_text(t.text())?,
+                DomNode::Comment(c) => serializer.write_comment(c.comment())?,
+                DomNode::DocType(d) => serializer.write_doctype(d.name())?,
+                DomNode::ProcessingInstruction(pi) => {
+                    serializer.write_processing_instruction(pi.target(), pi.data())?
+                }
+            },
+            Step::Close(n) => {
+                if let DomNode::Element(e) = &n.data {
+                    serializer.end_elem(e.name().clone())?
+                }
+            }
+            Step::Blank(b) => serializer.write_text(b)?,
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> Serialize for ElementRef<'a> {
+    fn serialize<S>(&self, serializer: &mut S, traversal_scope: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        serialize_subtree(
+            self.tree,
+            self.node,
+            serializer,
+            traversal_scope,
+            None,
+            &HtmlSerializeOptions::default(),
+        )
+    }
+}
+
+/// Wrap ElementRef to serialize it with max depth
+struct DepthLimited<'r, 'a> {
+    element: &'r ElementRef<'a>,
+    max_depth: usize,
+}
+
+impl<'r, 'a> Serialize for DepthLimited<'r, 'a> {
+    fn serialize<S>(&self, serializer: &mut S, traversal_scope: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        serialize_subtree(
+            self.element.tree,
+            self.element.node,
+            serializer,
+            traversal_scope,
+            Some(self.max_depth),
+            &HtmlSerializeOptions::default(),
+        )
+    }
+}
+
+/// Wrap ElementRef to serialize it with options
+struct WithOptions<'r, 'a> {
+    element: &'r ElementRef<'a>,
+    opts: &'r HtmlSerializeOptions,
+}
+
+impl<'r, 'a> Serialize for WithOptions<'r, 'a> {
+    fn serialize<S>(&self, serializer: &mut S, traversal_scope: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        serialize_subtree(
+            self.element.tree,
+            self.element.node,
+            serializer,
+            traversal_scope,
+            None,
+            self.opts,
+        )
+    }
+}
+
+fn serialize_to_string<T: Serialize>(
+    node: &T,
+    traversal_scope: TraversalScope,
+) -> io::Result<String> {
+    let mut buf = Vec::new();
+    serialize(
+        &mut buf,
+        node,
+        SerializeOpts {
+            traversal_scope,
+            ..Default::default()
+        },
+    )?;
+    // serializer only writes valid utf-8
+    Ok(String::from_utf8(buf).unwrap())
+}
+
+impl<'a> ElementRef<'a> {
+    /// Serialize the element and its subtree as HTML
+    pub fn html(&self) -> String {
+        // writing into Vec never fails
+        serialize_to_string(self, TraversalScope::IncludeNode).unwrap()
+    }
+
+    /// Serialize the element and its subtree as HTML with the options
+    pub fn html_with(&self, opts: &HtmlSerializeOptions) -> String {
+        serialize_to_string(
+            &WithOptions {
+                element: self,
+                opts,
+            },
+            TraversalScope::IncludeNode,
+        )
+        .unwrap()
+    }
+
+    /// Serialize only the subtree of the element as HTML
+    pub fn inner_html(&self) -> String {
+        serialize_to_string(self, TraversalScope::ChildrenOnly(self.name().cloned())).unwrap()
+    }
+
+    /// Serialize the element and its subtree as HTML, failing with an InvalidData error when
+    /// any node is nested deeper than max_depth below the element.
+    pub fn try_html(&self, max_depth: usize) -> io::Result<String> {
+        serialize_to_string(
+            &DepthLimited {
+                element: self,
+                max_depth,
+            },
+            TraversalScope::IncludeNode,
+        )
+    }
+}
+
+impl Html {
+    /// Serialize the whole document as HTML, escaping text like `ElementRef::html`
+    pub fn html(&self) -> String {
+        match self.root() {
+            ElementOrTextRef::Element(root) => root.html(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Escape text like the serializer does for text outside `<script>` and `<style>`
+fn escape_text(text: &str) -> String {
+    let mut buf = Vec::new();
+    // writing into Vec never fails, and serializer only writes valid utf-8
+    HtmlSerializer::new(&mut buf, SerializeOpts::default())
+        .write_text(text)
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+impl<'a> ElementOrTextRef<'a> {
+    /// Serialize the node alone as HTML: the outer HTML for Element and PhantomElement nodes, and
+    /// the escaped text for Text and PhantomText nodes
+    pub fn to_html(&self) -> String {
+        match self {
+            ElementOrTextRef::Element(e) => e.html(),
+            ElementOrTextRef::PhantomElement(e) => e.element().html(),
+            ElementOrTextRef::Text(t) => escape_text(t.text().text()),
+            ElementOrTextRef::PhantomText(t) => escape_text(t.text().text()),
+        }
+    }
+}
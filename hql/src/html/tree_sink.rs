@@ -1,13 +1,17 @@
+use std::{collections::HashMap, ops::Range};
+
 use html5ever::{
     expanded_name,
-    tree_builder::{ElementFlags, NodeOrText, TreeSink},
-    QualName,
+    tendril::StrTendril,
+    tokenizer::{TagKind, Token, TokenSink, TokenSinkResult},
+    tree_builder::{ElementFlags, NodeOrText, TreeBuilder, TreeSink},
+    LocalName, QualName,
 };
 use tracing::error;
 
 use crate::{
     html::dom::{DomNode, Element},
-    tree::NodeID,
+    tree::{ChildrenTraverse, NodeID, PreOrderTraverse, Tree},
 };
 
 use super::{
@@ -15,12 +19,225 @@ use super::{
     Html,
 };
 
+/// SourceTracker follows the position of each token in the input, to record the byte ranges of
+/// elements for `HtmlParseOptions::track_raw_source`. The input is fed in chunks split before each
+/// `<` and after each `>`, see `Html::parse_tracked`, so a tag token ends with the chunk it is
+/// emitted in, and starts at the first `<` since the previous token.
+///
+/// The tree builder does not report every element it closes, so only where start tags, text and
+/// comments end is recorded while parsing. The end of each element is derived from them once
+/// parsing finishes, see `SourceTracker::apply`.
+#[derive(Debug)]
+pub(crate) struct SourceTracker {
+    input: StrTendril,
+    // end of the input fed so far
+    fed: usize,
+    // range of the token being processed by the tree builder
+    token: Range<usize>,
+    // whether the token is text
+    text: bool,
+    // name of the token if it is a start tag
+    start_tag: Option<LocalName>,
+    // element created for the start tag so far
+    opened: Option<NodeID>,
+    // start of elements with a start tag
+    starts: HashMap<NodeID, usize>,
+    // end of start tags, text and comments
+    ends: HashMap<NodeID, usize>,
+}
+
+impl SourceTracker {
+    pub(crate) fn new(input: StrTendril) -> Self {
+        Self {
+            input,
+            fed: 0,
+            token: 0..0,
+            text: false,
+            start_tag: None,
+            opened: None,
+            starts: HashMap::new(),
+            ends: HashMap::new(),
+        }
+    }
+
+    /// Note the input up to end is about to be fed
+    pub(crate) fn advance(&mut self, end: usize) {
+        self.fed = end;
+    }
+
+    /// Note the token is about to be processed by the tree builder
+    fn begin(&mut self, token: &Token) {
+        let last = self.token.end;
+        let (start, text) = match token {
+            Token::ParseError(_) => return,
+            Token::CharacterTokens(_) | Token::NullCharacterToken => (last, true),
+            Token::EOFToken => (self.fed, false),
+            Token::TagToken(_) | Token::CommentToken(_) | Token::DoctypeToken(_) => {
+                let start = self.input[last..self.fed]
+                    .find('<')
+                    .map_or(last, |i| last + i);
+                (start, false)
+            }
+        };
+        self.token = start..self.fed;
+        self.text = text;
+        self.start_tag = match token {
+            Token::TagToken(tag) if tag.kind == TagKind::StartTag => Some(tag.name.clone()),
+            _ => None,
+        };
+        self.opened = None;
+    }
+
+    /// Note the element was created, which starts with the current token if it is its start tag.
+    /// Otherwise the element is implied by the parser, and has no source of its own.
+    fn open(&mut self, id: NodeID, name: &QualName) {
+        if !self
+            .start_tag
+            .as_ref()
+            .is_some_and(|tag| tag.eq_ignore_ascii_case(&name.local))
+        {
+            return;
+        }
+        // formatting elements reopened before the one of the tag share its name
+        if let Some(prev) = self.opened.replace(id) {
+            self.starts.remove(&prev);
+            self.ends.remove(&prev);
+        }
+        self.starts.insert(id, self.token.start);
+        self.ends.insert(id, self.token.end);
+    }
+
+    /// Note the node received text. Text flushed by a later token, like text pending in a table,
+    /// ends before that token.
+    fn extend(&mut self, id: NodeID) {
+        let end = match self.text {
+            true => self.token.end,
+            false => self.token.start,
+        };
+        self.ends.insert(id, end);
+    }
+
+    /// Set the source of each element with a start tag. Its content ends with the last descendant,
+    /// and it ends with its end tag following the content, if any.
+    fn apply(self, nodes: &mut Tree<DomNode>) {
+        let root = nodes.root_ref().unwrap();
+        let order = PreOrderTraverse::new(nodes, root)
+            .map(|(n, _)| n.id)
+            .collect::<Vec<_>>();
+
+        // children are visited before parents in reverse pre-order
+        let mut ends = HashMap::new();
+        let mut spans = vec![];
+        for id in order.into_iter().rev() {
+            let node = nodes.node_ref(id).unwrap();
+            let content_end = ChildrenTraverse::new(nodes, node, false)
+                .filter_map(|(c, _)| ends.get(&c.id).copied())
+                .chain(self.ends.get(&id).copied())
+                .max();
+            let (Some(content_end), Some(&start)) = (content_end, self.starts.get(&id)) else {
+                if let Some(end) = content_end {
+                    ends.insert(id, end);
+                }
+                continue;
+            };
+
+            let name = node.data.as_element().unwrap().name();
+            let rest = &self.input[content_end..];
+            let blank = rest.len()
+                - rest
+                    .trim_start_matches(|c: char| c.is_ascii_whitespace())
+                    .len();
+            let end = match end_tag_len(&rest[blank..], name) {
+                Some(len) => content_end + blank + len,
+                None => content_end,
+            };
+            ends.insert(id, end);
+            spans.push((id, start..end));
+        }
+
+        for (id, range) in spans {
+            let raw = self
+                .input
+                .subtendril(range.start as u32, (range.end - range.start) as u32);
+            if let DomNode::Element(e) = &mut nodes.node_mut_ref(id).unwrap().data {
+                e.set_source(range, raw);
+            }
+        }
+    }
+}
+
+/// Length of the end tag of name at the start of s, up to and including its closing `>`
+fn end_tag_len(s: &str, name: &QualName) -> Option<usize> {
+    let rest = s.strip_prefix("</")?;
+    let tag = rest.get(..name.local.len())?;
+    let after = &rest[name.local.len()..];
+    if !tag.eq_ignore_ascii_case(&name.local)
+        || !after.starts_with(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+    {
+        return None;
+    }
+    s.find('>').map(|i| i + 1)
+}
+
+/// Token sink passing tokens on to the tree builder, noting the position of each token for the
+/// SourceTracker of the Html being built
+pub(crate) struct SourceTokenSink {
+    pub(crate) builder: TreeBuilder<NodeID, Html>,
+}
+
+impl TokenSink for SourceTokenSink {
+    type Handle = NodeID;
+
+    fn process_token(&mut self, token: Token, line_number: u64) -> TokenSinkResult<NodeID> {
+        if let Some(s) = self.builder.sink.source.as_mut() {
+            s.begin(&token);
+        }
+        self.builder.process_token(token, line_number)
+    }
+
+    fn end(&mut self) {
+        self.builder.end()
+    }
+
+    fn adjusted_current_node_present_but_not_in_html_namespace(&self) -> bool {
+        self.builder
+            .adjusted_current_node_present_but_not_in_html_namespace()
+    }
+}
+
+impl Html {
+    /// Note node received text, see `SourceTracker::extend`
+    fn extend_source(&mut self, id: NodeID) {
+        if let Some(s) = self.source.as_mut() {
+            s.extend(id);
+        }
+    }
+
+    /// Note node was just parsed whole, like comments
+    fn end_source(&mut self, id: NodeID) {
+        if let Some(s) = self.source.as_mut() {
+            s.ends.insert(id, s.token.end);
+        }
+    }
+}
+
 impl TreeSink for Html {
     type Handle = NodeID;
 
     type Output = Self;
 
-    fn finish(self) -> Self::Output {
+    fn finish(mut self) -> Self::Output {
+        if let Some(s) = self.source.take() {
+            s.apply(&mut self.nodes);
+        }
+        if self.opts.drop_whitespace {
+            self.drop_whitespace_text();
+        }
+        // the tree builder recognizes foreign elements like `foreignObject` by their names, so
+        // names are only lowercased after parsing
+        if self.opts.lowercase_foreign_names {
+            self.lowercase_foreign_names();
+        }
         self
     }
 
@@ -56,6 +273,18 @@ impl TreeSink for Html {
             .orphan_node(DomNode::Element(Element::new(name, attrs)))
             .id;
 
+        if let Some(s) = self.source.as_mut() {
+            let name = self
+                .nodes
+                .node_ref(node_id)
+                .unwrap()
+                .data
+                .as_element()
+                .unwrap()
+                .name();
+            s.open(node_id, name);
+        }
+
         if is_fragment {
             self.nodes.append_child(node_id, DomNode::Fragment);
         }
@@ -64,9 +293,12 @@ impl TreeSink for Html {
     }
 
     fn create_comment(&mut self, text: html5ever::tendril::StrTendril) -> Self::Handle {
-        self.nodes
+        let id = self
+            .nodes
             .orphan_node(DomNode::Comment(Comment::new(text)))
-            .id
+            .id;
+        self.end_source(id);
+        id
     }
 
     fn create_pi(
@@ -74,11 +306,14 @@ impl TreeSink for Html {
         target: html5ever::tendril::StrTendril,
         data: html5ever::tendril::StrTendril,
     ) -> Self::Handle {
-        self.nodes
+        let id = self
+            .nodes
             .orphan_node(DomNode::ProcessingInstruction(ProcessingInstruction::new(
                 target, data,
             )))
-            .id
+            .id;
+        self.end_source(id);
+        id
     }
 
     fn append(&mut self, parent: &Self::Handle, child: NodeOrText<Self::Handle>) {
@@ -87,6 +322,7 @@ impl TreeSink for Html {
                 self.nodes.append_child_id(*parent, n).unwrap();
             }
             NodeOrText::AppendText(txt) => {
+                self.extend_source(*parent);
                 if let Some((_, last)) = self.nodes.children_range(*parent) {
                     if let DomNode::Text(last_node) =
                         &mut self.nodes.node_mut_ref(last).unwrap().data
@@ -154,6 +390,9 @@ impl TreeSink for Html {
                     self.nodes.insert_id_before(*sibling, new_node_id);
                 }
                 NodeOrText::AppendText(txt) => {
+                    if let Some(parent) = self.nodes.parent_ref(*sibling).map(|p| p.id) {
+                        self.extend_source(parent);
+                    }
                     if let Some(old_prev_sib) = self.nodes.previous_sibling_ref(*sibling) {
                         if let DomNode::Text(t) =
                             &mut self.nodes.node_mut_ref(old_prev_sib.id).unwrap().data
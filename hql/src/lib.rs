@@ -10,6 +10,24 @@
 extern crate html5ever;
 
 pub mod html;
+pub mod prelude;
 pub mod querier;
 pub mod selector;
 pub(crate) mod tree;
+
+/// Install a tracing subscriber printing to the test output, filtered by `RUST_LOG` with `info` as
+/// default. Tests run in one binary, so it may be called many times, and only the first call
+/// installs the subscriber.
+#[cfg(test)]
+pub(crate) fn init_test_tracing() {
+    use tracing::level_filters::LevelFilter;
+
+    let _ = tracing_subscriber::fmt::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .with_test_writer()
+        .try_init();
+}
@@ -0,0 +1,15 @@
+//! Commonly used types, to be glob imported by `use hql::prelude::*;`
+//!
+//! It is mainly helpful when constructing selectors manually instead of parsing HQL.
+
+pub use crate::{
+    html::{
+        ElementOrTextRef, ElementRef, Html, HtmlBuilder, HtmlParseOptions, HtmlSerializeOptions,
+        PhantomElementRef, PhantomTextRef, QueryResult, ResultKind, TextRef,
+    },
+    querier::{Groups, Querier, QuerierCache, QueryError},
+    selector::{attr::*, path::*, sibling::*, text::*, try_parse_hql, Selector, SelectorEnum},
+};
+
+#[cfg(feature = "serde")]
+pub use crate::selector::json::*;
@@ -1,25 +1,82 @@
-use std::fmt::Debug;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
 
 use tracing::info;
 
 use crate::{
-    html::{ElementOrTextRef, Html},
-    selector::{self, Rule, Selector, SelectorEnum},
+    html::{ElementOrTextRef, Html, NodeID, QueryResult},
+    selector::{self, HqlError, Selector, SelectorEnum},
 };
 
-#[derive(Debug)]
+/// QueryError is returned by queries requiring non-empty results, like `Querier::query_document_required`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// The selector at 0-based stage of the pipeline yields no nodes. selector is its `Debug` form
+    EmptyStage { stage: usize, selector: String },
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::EmptyStage { stage, selector } => {
+                write!(f, "stage {} yields no nodes: {}", stage, selector)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Results grouped by key, in the order each key is first seen. See `Querier::group_by`
+pub type Groups<'a> = Vec<(String, Vec<ElementOrTextRef<'a>>)>;
+
+#[derive(Debug, Clone)]
 pub struct Querier {
     pub selectors: Vec<SelectorEnum>,
 }
 
 impl Querier {
     #[allow(clippy::result_large_err)]
-    pub fn try_parse(hql: &str) -> Result<Self, pest::error::Error<Rule>> {
+    pub fn try_parse(hql: &str) -> Result<Self, HqlError> {
         Ok(Self {
             selectors: selector::try_parse_hql(hql)?,
         })
     }
 
+    /// Parse a pipeline given stage by stage, where each stage is a single selector like `@flat()`,
+    /// without `|`. The error of an invalid stage names its 0-based index, like `--> stage 1:1:1`.
+    ///
+    /// ```
+    /// use hql::querier::Querier;
+    ///
+    /// let q = Querier::try_parse_stages(["@path(`//a`)", "#attr(`href`)"]).unwrap();
+    /// assert_eq!(q.len(), 2);
+    ///
+    /// let err = Querier::try_parse_stages(["@flat()", "@path(`//a`) | #text()"]).unwrap_err();
+    /// assert!(err.to_string().contains("stage 1"));
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn try_parse_stages<I, S>(stages: I) -> Result<Self, HqlError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Ok(Self {
+            selectors: stages
+                .into_iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    selector::try_parse_stage(s.as_ref())
+                        .map_err(|e| e.with_path(&format!("stage {i}")))
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
     pub fn new(selectors: Vec<SelectorEnum>) -> Self {
         Self { selectors }
     }
@@ -28,23 +85,919 @@ impl Querier {
         self.selectors.push(s);
     }
 
-    pub fn query_document<'a, 'b: 'a>(&'b self, doc: &'a Html) -> Vec<ElementOrTextRef<'a>> {
+    pub fn selectors(&self) -> &[SelectorEnum] {
+        &self.selectors
+    }
+
+    /// Number of selectors, i.e. stages of the pipeline
+    pub fn len(&self) -> usize {
+        self.selectors.len()
+    }
+
+    /// Whether there is no selector, so that querying yields the starting node itself
+    pub fn is_empty(&self) -> bool {
+        self.selectors.is_empty()
+    }
+
+    /// Kind of each selector in pipeline order, like `["PathSelector", "TextSelector"]`. See
+    /// `Selector::kind_name`
+    pub fn kinds(&self) -> Vec<&'static str> {
+        self.selectors.iter().map(|s| s.kind_name()).collect()
+    }
+
+    /// Concatenate selectors of other after the ones of self, building a single pipeline.
+    ///
+    /// The whole output set of self flows into the first selector of other, exactly as if both
+    /// HQL were written in one statement joined by `|`.
+    pub fn chain(mut self, other: Querier) -> Querier {
+        self.selectors.extend(other.selectors);
+        self
+    }
+
+    /// Query document with self, then re-seed other with each of the results as its starting node.
+    ///
+    /// Unlike `chain`, other is evaluated as an independent query per result of self, and the
+    /// outputs are concatenated in order. Selectors of other never see results from other seeds,
+    /// so batch-level selectors like `#index()` restart for each seed.
+    pub fn then<'a>(&self, other: &Querier, doc: &'a Html) -> Vec<ElementOrTextRef<'a>> {
+        self.query_document(doc)
+            .into_iter()
+            .flat_map(|n| other.query_node(n))
+            .collect()
+    }
+
+    /// Query document with self, then group the results by key, which is evaluated per result like
+    /// `then` and concatenated like `results_to_string` without separator.
+    ///
+    /// Groups are in the order their keys are first seen, and each keeps results in query order.
+    /// Results for which key yields nothing are grouped under the empty key.
+    pub fn group_by<'a>(&self, key: &Querier, doc: &'a Html) -> Groups<'a> {
+        let mut groups: Groups<'a> = vec![];
+        let mut index = HashMap::new();
+
+        for n in self.query_document(doc) {
+            let k = Self::results_to_string(&key.query_node(n.clone()), "");
+            let i = *index.entry(k.clone()).or_insert_with(|| {
+                groups.push((k, vec![]));
+                groups.len() - 1
+            });
+            groups[i].1.push(n);
+        }
+
+        groups
+    }
+
+    /// Query document from its root.
+    ///
+    /// Both self and doc are borrowed immutably, so one parsed `Html` can be queried by many
+    /// `Querier`s without re-parsing, and results of different queriers can be held at the same time.
+    pub fn query_document<'a>(&self, doc: &'a Html) -> Vec<ElementOrTextRef<'a>> {
+        self.query_node(doc.root())
+    }
+
+    /// Query document like `query_document`, but fail with the first stage yielding no nodes instead
+    /// of returning an empty result. It is helpful to detect changed page layouts.
+    pub fn query_document_required<'a>(
+        &self,
+        doc: &'a Html,
+    ) -> Result<Vec<ElementOrTextRef<'a>>, QueryError> {
         let mut nodes = vec![doc.root()];
 
+        for (stage, s) in self.selectors.iter().enumerate() {
+            info!("apply selector: {:?}", s);
+            nodes = s.select_batch(nodes);
+            if nodes.is_empty() {
+                return Err(QueryError::EmptyStage {
+                    stage,
+                    selector: format!("{:?}", s),
+                });
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// Query starting from node instead of the document root
+    pub fn query_node<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let mut nodes = vec![node];
+
         for s in &self.selectors {
             info!("apply selector: {:?}", s);
-            nodes = nodes
-                .into_iter()
-                .flat_map(|n| s.select(n))
-                .collect::<Vec<_>>();
+            nodes = s.select_batch(nodes);
         }
 
         nodes
     }
+
+    /// Join results by sep, with text for Text and PhantomText nodes and HTML for Element nodes.
+    ///
+    /// ```
+    /// use hql::{html::Html, querier::Querier};
+    ///
+    /// let doc = Html::parse_fragment("<a>x</a><a>y</a>", false);
+    /// let q = Querier::try_parse("@path(`//a`) | #text()").unwrap();
+    ///
+    /// assert_eq!(Querier::results_to_string(&q.query_document(&doc), "\n"), "x\ny");
+    /// // or simply concatenate them
+    /// assert_eq!(q.query_document(&doc).into_iter().collect::<String>(), "xy");
+    /// ```
+    pub fn results_to_string(results: &[ElementOrTextRef], sep: &str) -> String {
+        results
+            .iter()
+            .map(|n| n.as_text_cow())
+            .collect::<Vec<_>>()
+            .join(sep)
+    }
+
+    /// Return the lowest element containing all nodes, which is the element itself for a single
+    /// element, like to scope a follow-up query with `query_node`. Text nodes are contained by their
+    /// parent element.
+    ///
+    /// None is returned for an empty node set, or when any node is phantom or the nodes share no
+    /// element, like nodes of different documents.
+    pub fn common_ancestor<'a>(nodes: &[ElementOrTextRef<'a>]) -> Option<ElementOrTextRef<'a>> {
+        let chains = nodes
+            .iter()
+            .map(|n| {
+                let start = match n {
+                    ElementOrTextRef::Element(e) => Some(e.clone()),
+                    _ => n.parent(),
+                }?;
+                Some(
+                    std::iter::once(start.clone())
+                        .chain(start.ancestors())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let (first, rest) = chains.split_first()?;
+        first
+            .iter()
+            .find(|e| rest.iter().all(|chain| chain.contains(e)))
+            .map(|e| ElementOrTextRef::Element(e.clone()))
+    }
+
+    /// Return the first result of querying document, evaluating selectors lazily.
+    ///
+    /// It stops as soon as the last selector yields one node, so generating selectors like `@flat()`
+    /// do not traverse the whole subtree. Batch-level selectors like `#index()` need the whole node
+    /// set, so selectors up to the last batch-level one are evaluated eagerly.
+    pub fn query_first<'a>(&self, doc: &'a Html) -> Option<ElementOrTextRef<'a>> {
+        self.query_lazy(doc).next()
+    }
+
+    /// Return the window of results from offset with at most limit nodes, like a page of a UI, and
+    /// the total number of results.
+    ///
+    /// Selectors are evaluated lazily like `query_first`, and results outside the window are only
+    /// counted, not collected. Counting the total still evaluates the whole query, so it costs as
+    /// much time as `query_document`, though not its memory for the results.
+    pub fn query_document_page<'a>(
+        &self,
+        doc: &'a Html,
+        offset: usize,
+        limit: usize,
+    ) -> (Vec<ElementOrTextRef<'a>>, usize) {
+        let mut page = vec![];
+        let mut total = 0;
+        for n in self.query_lazy(doc) {
+            if total >= offset && total - offset < limit {
+                page.push(n);
+            }
+            total += 1;
+        }
+        (page, total)
+    }
+
+    /// Iterate results of querying document, evaluating selectors after the last batch-level one
+    /// lazily, since batch-level selectors need the whole node set
+    fn query_lazy<'a, 'b>(
+        &'b self,
+        doc: &'a Html,
+    ) -> impl Iterator<Item = ElementOrTextRef<'a>> + 'b
+    where
+        'a: 'b,
+    {
+        let split = self
+            .selectors
+            .iter()
+            .rposition(|s| s.is_batch())
+            .map_or(0, |i| i + 1);
+        let (eager, lazy) = self.selectors.split_at(split);
+
+        let mut nodes = vec![doc.root()];
+        for s in eager {
+            nodes = s.select_batch(nodes);
+        }
+
+        nodes.into_iter().flat_map(move |n| Self::iter_of(lazy, n))
+    }
+
+    fn iter_of<'a: 'i, 'b: 'i, 'i>(
+        selectors: &'b [SelectorEnum],
+        node: ElementOrTextRef<'a>,
+    ) -> Box<dyn Iterator<Item = ElementOrTextRef<'a>> + 'i> {
+        match selectors.split_first() {
+            None => Box::new(std::iter::once(node)),
+            Some((s, rest)) => Box::new(s.select_iter(node).flat_map(|n| Self::iter_of(rest, n))),
+        }
+    }
+
+    /// Query document like `query_document`, but stably sort the results in document order.
+    /// See `ElementOrTextRef::document_position` for the ordering.
+    pub fn query_document_in_order<'a>(&self, doc: &'a Html) -> Vec<ElementOrTextRef<'a>> {
+        let mut nodes = self.query_document(doc);
+        nodes.sort_by_key(|n| n.document_position());
+        nodes
+    }
+
+    /// Query document like `query_document`, and annotate each result with its
+    /// `ElementOrTextRef::document_position` and `ElementOrTextRef::depth`, like for breadcrumbs.
+    /// Phantom nodes get `NodeID::from(usize::MAX)` and depth 0.
+    pub fn query_document_annotated<'a>(
+        &self,
+        doc: &'a Html,
+    ) -> Vec<(ElementOrTextRef<'a>, NodeID, usize)> {
+        self.query_document(doc)
+            .into_iter()
+            .map(|n| {
+                let (id, depth) = (n.document_position(), n.depth());
+                (n, id, depth)
+            })
+            .collect()
+    }
+
+    /// Query document like `query_document`, and pair each result with the CSS-like path of tags
+    /// leading to it, like `html>body>div#main.content>a`, for debugging queries or writing stable
+    /// ones. Each segment is the tag followed by `#id` and `.class` qualifiers when present. Text
+    /// nodes get the path of their parent element, and phantom nodes an empty path.
+    pub fn query_document_with_paths<'a>(
+        &self,
+        doc: &'a Html,
+    ) -> Vec<(ElementOrTextRef<'a>, String)> {
+        self.query_document(doc)
+            .into_iter()
+            .map(|n| {
+                let path = css_path(&n);
+                (n, path)
+            })
+            .collect()
+    }
+
+    /// Query document like `query_document`, and copy the results into a new `Html`, like a trimmed
+    /// copy of the page. See `Html::from_nodes`
+    pub fn extract_to_html(&self, doc: &Html) -> Html {
+        Html::from_nodes(&self.query_document(doc))
+    }
+
+    /// Query document like `query_document`, and write each result as a CSV row of a single field,
+    /// which is the text for Text and PhantomText nodes and the HTML for Element nodes like
+    /// `results_to_string`. Fields are quoted per RFC 4180, and rows end with CRLF.
+    pub fn query_document_csv(&self, doc: &Html) -> String {
+        self.query_document(doc)
+            .iter()
+            .map(|n| format!("{}\r\n", csv_field(&n.as_text_cow())))
+            .collect()
+    }
+
+    /// Query document with each querier and write a single CSV row with one field per querier, like
+    /// a record scraped from a detail page. Results of a querier are joined by `\n` like
+    /// `results_to_string`, so a querier yielding nothing makes an empty field.
+    ///
+    /// ```
+    /// use hql::{html::Html, querier::Querier};
+    ///
+    /// let doc = Html::parse_fragment(r#"<h1>Tea, green</h1><span class="price">3</span>"#, false);
+    /// let columns = ["@path(`//h1`) | #text()", "@path(`//span`) | @class(`price`) | #text()"]
+    ///     .map(|hql| Querier::try_parse(hql).unwrap());
+    /// assert_eq!(Querier::query_document_csv_row(&columns, &doc), "\"Tea, green\",3\r\n");
+    /// ```
+    pub fn query_document_csv_row(queriers: &[Querier], doc: &Html) -> String {
+        let fields = queriers
+            .iter()
+            .map(|q| csv_field(&Self::results_to_string(&q.query_document(doc), "\n")).into_owned())
+            .collect::<Vec<_>>();
+        format!("{}\r\n", fields.join(","))
+    }
+
+    /// Query document like `query_document`, but map results into owned `QueryResult`s
+    /// which do not borrow the document.
+    pub fn query_document_results(&self, doc: &Html) -> Vec<QueryResult> {
+        self.query_document(doc)
+            .iter()
+            .map(QueryResult::from)
+            .collect()
+    }
+}
+
+/// Quote a CSV field per RFC 4180 if it contains a comma, a double quote or a line break, doubling
+/// double quotes inside
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Path of element tags from the document root down to node, see `Querier::query_document_with_paths`
+fn css_path(node: &ElementOrTextRef) -> String {
+    let element = match node {
+        ElementOrTextRef::Element(e) => Some(e.clone()),
+        ElementOrTextRef::Text(_) => node.parent(),
+        ElementOrTextRef::PhantomElement(_) | ElementOrTextRef::PhantomText(_) => None,
+    };
+    let mut chain = element
+        .into_iter()
+        .flat_map(|e| std::iter::once(e.clone()).chain(e.ancestors()))
+        // the document root has no tag
+        .filter_map(|e| {
+            let mut segment = e.name()?.local.to_string();
+            if let Some(id) = e.attr_str("id").filter(|id| !id.is_empty()) {
+                segment.push('#');
+                segment.push_str(id);
+            }
+            for class in e
+                .attr_str("class")
+                .into_iter()
+                .flat_map(str::split_whitespace)
+            {
+                segment.push('.');
+                segment.push_str(class);
+            }
+            Some(segment)
+        })
+        .collect::<Vec<_>>();
+    chain.reverse();
+    chain.join(">")
+}
+
+/// QuerierCache memoizes parsed `Querier`s by their HQL, for apps running the same few queries
+/// repeatedly. Queriers are shared by `Rc`, so a cache hit neither reparses nor copies selectors.
+///
+/// The cache is unbounded. Errors are not cached, so invalid HQL is parsed again on each call.
+///
+/// ```
+/// use hql::{html::Html, querier::QuerierCache};
+///
+/// let mut cache = QuerierCache::new();
+/// let doc = Html::parse_fragment("<a>x</a>", false);
+/// for _ in 0..3 {
+///     let q = cache.get_or_parse("@path(`//a`) | #text()").unwrap();
+///     assert_eq!(q.query_document(&doc).len(), 1);
+/// }
+/// assert_eq!(cache.len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct QuerierCache {
+    queriers: HashMap<String, Rc<Querier>>,
+}
+
+impl QuerierCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the cached querier of hql, parsing and caching it on the first call
+    #[allow(clippy::result_large_err)]
+    pub fn get_or_parse(&mut self, hql: &str) -> Result<Rc<Querier>, HqlError> {
+        if let Some(q) = self.queriers.get(hql) {
+            return Ok(q.clone());
+        }
+
+        let q = Rc::new(Querier::try_parse(hql)?);
+        self.queriers.insert(hql.to_string(), q.clone());
+        Ok(q)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queriers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queriers.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queriers.clear();
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::{
+        html::{ElementOrTextRef, Html, HtmlBuilder, NodeID, QueryResult, ResultKind},
+        tree::PRE_ORDER_VISITED,
+    };
+
+    use std::rc::Rc;
+
+    use super::{Querier, QuerierCache, QueryError};
+
+    fn texts(nodes: &[ElementOrTextRef]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|n| QueryResult::from(n).text().to_string())
+            .collect()
+    }
+
     #[test]
     fn test_parse() {}
+
+    #[test]
+    fn test_introspection() {
+        let q =
+            Querier::try_parse("@path(`//a`) | @attr(`href`) | #attr(`href`) | #trim()").unwrap();
+        assert_eq!(q.len(), 4);
+        assert!(!q.is_empty());
+        assert_eq!(q.selectors().len(), 4);
+        assert_eq!(
+            q.kinds(),
+            vec![
+                "PathSelector",
+                "AttrSelector",
+                "ExtractAttrSelector",
+                "TrimSelector"
+            ]
+        );
+
+        let q = Querier::new(vec![]);
+        assert!(q.is_empty());
+        assert!(q.kinds().is_empty());
+    }
+
+    #[test]
+    fn test_chain_and_then() {
+        let doc = Html::parse_document(
+            r#"<html><body><ul><li>a</li><li>b</li></ul><div><ul><li>c</li></ul></div></body></html>"#,
+            false,
+        );
+
+        let chained = Querier::try_parse("@path(`//ul`)")
+            .unwrap()
+            .chain(Querier::try_parse("@path(`/li`) | #text()").unwrap());
+        let single = Querier::try_parse("@path(`//ul`) | @path(`/li`) | #text()").unwrap();
+        assert_eq!(chained.selectors, single.selectors);
+        assert_eq!(
+            texts(&chained.query_document(&doc)),
+            texts(&single.query_document(&doc))
+        );
+
+        let first = Querier::try_parse("@path(`//ul`)").unwrap();
+        let second = Querier::try_parse("@path(`/li`) | #text()").unwrap();
+        assert_eq!(texts(&first.then(&second, &doc)), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_try_parse_stages() {
+        let doc = Html::parse_fragment("<a href=\"/x\">x</a>", false);
+        let stages = vec!["@path(`//a`)".to_string(), "#attr(`href`)".to_string()];
+        let q = Querier::try_parse_stages(&stages).unwrap();
+        assert_eq!(texts(&q.query_document(&doc)), vec!["/x"]);
+        assert!(Querier::try_parse_stages(Vec::<&str>::new())
+            .unwrap()
+            .is_empty());
+
+        for (stages, index) in [
+            (vec!["@flat()", "@path(`a`)", "#text()"], 1),
+            (vec!["@flat()", "#text()", "#trim() | #text()"], 2),
+            (vec!["", "#text()"], 0),
+            (vec!["@flat()", "@child(99999999999999999999)"], 1),
+        ] {
+            let err = Querier::try_parse_stages(&stages).unwrap_err();
+            assert!(
+                err.to_string().contains(&format!("--> stage {index}:")),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_clone() {
+        let doc =
+            Html::parse_fragment(r#"<a href="/item/1">x</a><a href="/item/2"> y </a>"#, false);
+        let q = Querier::try_parse(
+            r"@path(`//a`) | @matchAttr(`href`, `/item/(\d+)`, 1) | #resolveUrl(`https://a.com/`)",
+        )
+        .unwrap();
+
+        let mut cloned = q.clone();
+        assert_eq!(cloned.selectors(), q.selectors());
+        assert_eq!(
+            texts(&cloned.query_document(&doc)),
+            texts(&q.query_document(&doc))
+        );
+
+        // the clone is independent of the template
+        cloned.add_selector(Querier::try_parse("#const(`z`)").unwrap().selectors[0].clone());
+        assert_eq!(texts(&cloned.query_document(&doc)), vec!["z", "z"]);
+        assert_eq!(
+            texts(&q.query_document(&doc)),
+            vec!["https://a.com/1", "https://a.com/2"]
+        );
+    }
+
+    #[test]
+    fn test_query_document_csv() {
+        let doc = Html::parse_fragment(
+            r#"<p>plain</p><p>a, b</p><p>say "hi"</p><p>x
+y</p><b>1</b><b>2</b>"#,
+            false,
+        );
+
+        let q = Querier::try_parse("@path(`//p`) | #text()").unwrap();
+        assert_eq!(
+            q.query_document_csv(&doc),
+            "plain\r\n\"a, b\"\r\n\"say \"\"hi\"\"\"\r\n\"x\ny\"\r\n"
+        );
+        // elements are written as HTML
+        let q = Querier::try_parse("@path(`//b`)").unwrap();
+        assert_eq!(q.query_document_csv(&doc), "<b>1</b>\r\n<b>2</b>\r\n");
+        assert_eq!(
+            Querier::try_parse("@path(`//i`)")
+                .unwrap()
+                .query_document_csv(&doc),
+            ""
+        );
+
+        let columns = [
+            "@path(`//p`) | #text() | @sample(2)",
+            "@path(`//b`) | #text()",
+            "@path(`//i`)",
+            "#const(`,`)",
+        ]
+        .map(|hql| Querier::try_parse(hql).unwrap());
+        assert_eq!(
+            Querier::query_document_csv_row(&columns, &doc),
+            "\"plain\nsay \"\"hi\"\"\",\"1\n2\",,\",\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_querier_cache() {
+        let mut cache = QuerierCache::new();
+        assert!(cache.is_empty());
+
+        let a = cache.get_or_parse("@path(`//a`) | #text()").unwrap();
+        let b = cache.get_or_parse("@path(`//a`) | #text()").unwrap();
+        // the hit shares the parsed pipeline instead of reparsing
+        assert!(Rc::ptr_eq(&a, &b));
+        assert_eq!(
+            a.selectors(),
+            Querier::try_parse("@path(`//a`) | #text()")
+                .unwrap()
+                .selectors()
+        );
+
+        let c = cache.get_or_parse("@path(`//b`)").unwrap();
+        assert!(!Rc::ptr_eq(&a, &c));
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.get_or_parse("@path(").is_err());
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(!Rc::ptr_eq(
+            &a,
+            &cache.get_or_parse("@path(`//a`) | #text()").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let doc = Html::parse_fragment(
+            r#"<div id="d"><ul id="u"><li><b>a</b></li><li>b</li></ul><p id="p">c</p></div>"#,
+            false,
+        );
+        let query = |hql| Querier::try_parse(hql).unwrap().query_document(&doc);
+        let id = |n: Option<ElementOrTextRef>| match n {
+            Some(ElementOrTextRef::Element(e)) => e
+                .attrs()
+                .find(|(k, _)| &k.local == "id")
+                .map(|(_, v)| v.to_string()),
+            _ => None,
+        };
+
+        // nodes in sibling subtrees share their parent
+        let nodes = query("@path(`//b`) | @child(0)")
+            .into_iter()
+            .chain(query("@path(`//li`) | @child(-1)").into_iter().skip(1))
+            .collect::<Vec<_>>();
+        assert_eq!(texts(&nodes), vec!["a", "b"]);
+        assert_eq!(id(Querier::common_ancestor(&nodes)), Some("u".into()));
+
+        let nodes = query("@path(`//b`)")
+            .into_iter()
+            .chain(query("@path(`//p`)"))
+            .collect::<Vec<_>>();
+        assert_eq!(id(Querier::common_ancestor(&nodes)), Some("d".into()));
+
+        // an ancestor of the other nodes is the common ancestor itself
+        assert_eq!(
+            id(Querier::common_ancestor(&query("@path(`//ul`) | @flat()"))),
+            Some("u".into())
+        );
+
+        assert!(Querier::common_ancestor(&[]).is_none());
+        assert!(Querier::common_ancestor(&query("@path(`//li`) | #text()")).is_none());
+
+        let other = Html::parse_fragment(r#"<div id="d"></div>"#, false);
+        let nodes = query("@path(`//div`)")
+            .into_iter()
+            .chain(
+                Querier::try_parse("@path(`//div`)")
+                    .unwrap()
+                    .query_document(&other),
+            )
+            .collect::<Vec<_>>();
+        assert!(Querier::common_ancestor(&nodes).is_none());
+    }
+
+    #[test]
+    fn test_query_document_with_paths() {
+        let doc = Html::parse_document(
+            r#"<html><body><div id="main" class="content wide"><p><a href="/x">x</a> y</p></div></body></html>"#,
+            false,
+        );
+        let paths = |hql| {
+            Querier::try_parse(hql)
+                .unwrap()
+                .query_document_with_paths(&doc)
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            paths("@path(`//a`)"),
+            vec!["html>body>div#main.content.wide>p>a"]
+        );
+        // text nodes get the path of their parent, phantom nodes an empty one
+        assert_eq!(
+            paths("@path(`//p`) | @flat()"),
+            vec![
+                "html>body>div#main.content.wide>p",
+                "html>body>div#main.content.wide>p>a",
+                "html>body>div#main.content.wide>p>a",
+                "html>body>div#main.content.wide>p",
+            ]
+        );
+        assert_eq!(paths("@path(`//a`) | #text()"), vec![""]);
+    }
+
+    #[test]
+    fn test_query_document_annotated() {
+        let doc = Html::builder()
+            .child(
+                HtmlBuilder::new()
+                    .element("ul")
+                    .child(HtmlBuilder::new().element("li").text("a")),
+            )
+            .child(HtmlBuilder::new().element("p").text("b"))
+            .build();
+        let annotate = |hql| {
+            Querier::try_parse(hql)
+                .unwrap()
+                .query_document_annotated(&doc)
+                .into_iter()
+                .map(|(n, id, depth)| (QueryResult::from(&n).text().to_string(), id, depth))
+                .collect::<Vec<_>>()
+        };
+
+        // 0 Fragment, 1 <ul>, 2 <li>, 3 "a", 4 <p>, 5 "b"
+        assert_eq!(
+            annotate("@path(`//li`) | @child(0)"),
+            vec![("a".to_string(), NodeID::from(3), 2)]
+        );
+        assert_eq!(
+            annotate("@path(`//ul`) | @path(`/li`)"),
+            vec![("a".to_string(), NodeID::from(2), 1)]
+        );
+        assert_eq!(
+            annotate("@path(`//p`) | #text()"),
+            vec![("b".to_string(), NodeID::from(usize::MAX), 0)]
+        );
+    }
+
+    #[test]
+    fn test_group_by() {
+        let doc = Html::parse_fragment(
+            r#"<li data-cat="b">1</li><li data-cat="a">2</li><li>3</li><li data-cat="b">4</li>"#,
+            false,
+        );
+
+        let items = Querier::try_parse("@path(`//li`)").unwrap();
+        let key = Querier::try_parse("#attr(`data-cat`)").unwrap();
+        let groups = items
+            .group_by(&key, &doc)
+            .into_iter()
+            .map(|(k, nodes)| (k, texts(&nodes)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                ("b".to_string(), vec!["1".to_string(), "4".to_string()]),
+                ("a".to_string(), vec!["2".to_string()]),
+                ("".to_string(), vec!["3".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_to_html() {
+        let doc = Html::parse_document(
+            r#"<html><body><nav>menu</nav><article id="a"><p>one</p></article><div><article id="b">two<br></article></div></body></html>"#,
+            false,
+        );
+
+        let extracted = Querier::try_parse("@path(`//article`)")
+            .unwrap()
+            .extract_to_html(&doc);
+        let html = match extracted.root() {
+            ElementOrTextRef::Element(e) => e.html(),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            html,
+            r#"<article id="a"><p>one</p></article><article id="b">two<br></article>"#
+        );
+
+        // the copy is independent of the original document
+        drop(doc);
+        let q = Querier::try_parse("@path(`//p`) | #text()").unwrap();
+        assert_eq!(texts(&q.query_document(&extracted)), vec!["one"]);
+    }
+
+    #[test]
+    fn test_reuse_document() {
+        let doc = Html::parse_document(
+            r#"<html><body><a href="/x">x</a><a href="/y">y</a></body></html>"#,
+            false,
+        );
+
+        let links = Querier::try_parse("@path(`//a`)").unwrap();
+        let hrefs = Querier::try_parse("@path(`//a`) | #attr(`href`)").unwrap();
+        let texts_q = Querier::try_parse("@path(`//a`) | #text()").unwrap();
+
+        let link_nodes = links.query_document(&doc);
+        let href_nodes = hrefs.query_document(&doc);
+        let text_nodes = texts_q.query_document(&doc);
+
+        assert_eq!(link_nodes.len(), 2);
+        assert_eq!(texts(&href_nodes), vec!["/x", "/y"]);
+        assert_eq!(texts(&text_nodes), vec!["x", "y"]);
+        // query again with the same querier over the same document
+        assert_eq!(texts(&hrefs.query_document(&doc)), texts(&href_nodes));
+    }
+
+    #[test]
+    fn test_query_document_page() {
+        let doc = Html::parse_fragment("<li>1</li><li>2</li><li>3</li><li>4</li><li>5</li>", false);
+        let q = Querier::try_parse("@path(`//li`) | #text()").unwrap();
+
+        let (page, total) = q.query_document_page(&doc, 0, 2);
+        assert_eq!((texts(&page), total), (vec!["1".into(), "2".into()], 5));
+        let (page, total) = q.query_document_page(&doc, 2, 2);
+        assert_eq!((texts(&page), total), (vec!["3".into(), "4".into()], 5));
+        // the last page is short
+        let (page, total) = q.query_document_page(&doc, 4, 2);
+        assert_eq!((texts(&page), total), (vec!["5".into()], 5));
+        // offset past the end
+        let (page, total) = q.query_document_page(&doc, 7, 2);
+        assert_eq!((page.len(), total), (0, 5));
+
+        // batch-level selectors see the whole node set
+        let q = Querier::try_parse("@path(`//li`) | #index() | #text()").unwrap();
+        let (page, total) = q.query_document_page(&doc, 3, 10);
+        assert_eq!((texts(&page), total), (vec!["3".into(), "4".into()], 5));
+    }
+
+    #[test]
+    fn test_query_first() {
+        let doc = Html::parse_document(
+            &format!(
+                "<html><body><p class=\"x\">first</p>{}</body></html>",
+                "<p class=\"x\">other</p>".repeat(1000)
+            ),
+            false,
+        );
+
+        let q = Querier::try_parse("@flat() | @class(`x`) | #text()").unwrap();
+
+        PRE_ORDER_VISITED.with(|v| v.set(0));
+        assert_eq!(texts(&q.query_document(&doc)).len(), 1001);
+        let all_visited = PRE_ORDER_VISITED.with(|v| v.get());
+
+        PRE_ORDER_VISITED.with(|v| v.set(0));
+        assert_eq!(
+            texts(&q.query_first(&doc).into_iter().collect::<Vec<_>>()),
+            vec!["first"]
+        );
+        let first_visited = PRE_ORDER_VISITED.with(|v| v.get());
+        assert!(
+            first_visited < 10,
+            "visited {} nodes, while querying all visits {}",
+            first_visited,
+            all_visited
+        );
+
+        // batch-level selectors still see the whole node set
+        let q = Querier::try_parse("@path(`//p`) | #index() | #trimPrefix(`x`)").unwrap();
+        assert_eq!(
+            texts(&q.query_first(&doc).into_iter().collect::<Vec<_>>()),
+            vec!["0"]
+        );
+
+        let q = Querier::try_parse("@path(`//span`)").unwrap();
+        assert!(q.query_first(&doc).is_none());
+    }
+
+    #[test]
+    fn test_query_document_required() {
+        // logs each applied selector, like test_parse_document of html also does
+        crate::init_test_tracing();
+
+        let doc = Html::parse_document(
+            r#"<html><body><a href="/a">a</a><a>b</a></body></html>"#,
+            false,
+        );
+
+        let q = Querier::try_parse("@path(`//a`) | @attr(`href`) | #attr(`href`)").unwrap();
+        assert_eq!(texts(&q.query_document_required(&doc).unwrap()), vec!["/a"]);
+
+        let q = Querier::try_parse("@path(`//a`) | @attr(`target`) | #attr(`href`)").unwrap();
+        let err = q.query_document_required(&doc).unwrap_err();
+        assert_eq!(
+            err,
+            QueryError::EmptyStage {
+                stage: 1,
+                selector: format!("{:?}", q.selectors[1]),
+            }
+        );
+        assert!(err.to_string().starts_with("stage 1 yields no nodes: "));
+    }
+
+    #[test]
+    fn test_query_document_in_order() {
+        let doc = Html::parse_document(
+            r#"<html><body><div><span>a</span><span>b</span></div></body></html>"#,
+            false,
+        );
+
+        let q = Querier::try_parse("@flat() | @path(`//span`)").unwrap();
+        assert_eq!(
+            texts(&q.query_document(&doc)),
+            vec!["a", "b", "a", "b", "a", "b", "a", "b"]
+        );
+        assert_eq!(
+            texts(&q.query_document_in_order(&doc)),
+            vec!["a", "a", "a", "a", "b", "b", "b", "b"]
+        );
+    }
+
+    #[test]
+    fn test_query_document_results() {
+        let doc = Html::parse_document(
+            r#"<html><body><a href="/x" target="_blank">link <b>bold</b></a></body></html>"#,
+            false,
+        );
+
+        let results = Querier::try_parse("@path(`//a`)")
+            .unwrap()
+            .query_document_results(&doc);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind(), ResultKind::Element);
+        assert_eq!(results[0].tag(), Some("a"));
+        assert_eq!(results[0].attr("href"), Some("/x"));
+        assert_eq!(results[0].attributes().len(), 2);
+        assert_eq!(results[0].text(), "link bold");
+
+        let results = Querier::try_parse("@path(`//a`) | @flat()")
+            .unwrap()
+            .query_document_results(&doc);
+        assert_eq!(
+            results.iter().map(|r| r.kind()).collect::<Vec<_>>(),
+            vec![
+                ResultKind::Element,
+                ResultKind::Text,
+                ResultKind::Element,
+                ResultKind::Text
+            ]
+        );
+        assert_eq!(results[1].tag(), None);
+        assert_eq!(results[1].text(), "link ");
+        assert_eq!(results[2].tag(), Some("b"));
+        assert_eq!(results[2].text(), "bold");
+
+        let results = Querier::try_parse("@path(`//a`) | #attr(`target`)")
+            .unwrap()
+            .query_document_results(&doc);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].kind(), ResultKind::Phantom);
+        assert!(results[0].attributes().is_empty());
+        assert_eq!(results[0].text(), "_blank");
+    }
 }
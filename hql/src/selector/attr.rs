@@ -1,36 +1,43 @@
-use std::str::FromStr;
+use std::cmp::Ordering;
 
 use html5ever::{tendril::StrTendril, LocalName, QualName};
+use regex::Regex;
 
-use crate::html::ElementOrTextRef;
+use crate::html::{ElementOrTextRef, ElementRef};
 
 use super::Selector;
 
-///
-#[derive(Debug, PartialEq)]
+/// AttrSelector filters Element nodes by attribute, dropping Text and PhantomText nodes. With
+/// values, the attribute value must equal any of them, ignoring ASCII case.
+#[derive(Debug, Clone, PartialEq)]
 pub struct AttrSelector {
     name: QualName,
-    /// val: none means filter whether attr:name exists
-    val: Option<StrTendril>,
+    /// vals: none means filter whether attr:name exists
+    vals: Option<Vec<StrTendril>>,
 }
 
 impl AttrSelector {
     pub fn new(name: &str, val: Option<&str>) -> Self {
+        Self::new_any_of(name, val.map(|v| vec![v]))
+    }
+
+    /// Match when the attribute equals any of vals, like ``@attr(`type`, [`button`, `submit`])``
+    pub fn new_any_of(name: &str, vals: Option<Vec<&str>>) -> Self {
         Self {
             name: QualName::new(None, ns!(), LocalName::from(name)),
-            val: val.map(|v| StrTendril::from_str(v).unwrap()),
+            vals: vals.map(|vals| vals.into_iter().map(StrTendril::from_slice).collect()),
         }
     }
 }
 
 impl Selector for AttrSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .filter(|n| match n {
                 ElementOrTextRef::Element(e) => {
-                    e.get_attr(&self.name).iter().any(|s| match &self.val {
+                    e.get_attr(&self.name).iter().any(|s| match &self.vals {
                         None => true,
-                        Some(v) => s.eq_ignore_ascii_case(v),
+                        Some(vals) => vals.iter().any(|v| s.eq_ignore_ascii_case(v)),
                     })
                 }
                 _ => false,
@@ -39,7 +46,141 @@ impl Selector for AttrSelector {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// SortByAttrSelector is a batch-level selector stably sorting the whole node set by the attribute
+/// value, either lexically or numerically. Nodes without the attribute, like text nodes, and values
+/// which are not numbers when sorting numerically, are placed last in their original order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortByAttrSelector {
+    name: QualName,
+    numeric: bool,
+}
+
+impl SortByAttrSelector {
+    pub fn new(name: &str, numeric: bool) -> Self {
+        Self {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            numeric,
+        }
+    }
+
+    fn value(&self, node: &ElementOrTextRef) -> Option<StrTendril> {
+        match node {
+            ElementOrTextRef::Element(e) => e.get_attr(&self.name).cloned(),
+            _ => None,
+        }
+    }
+}
+
+/// Compare keys, placing missing ones last
+fn cmp_missing_last<T>(a: Option<T>, b: Option<T>, cmp: impl Fn(&T, &T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl Selector for SortByAttrSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        let mut keyed = nodes
+            .into_iter()
+            .map(|n| (self.value(&n), n))
+            .collect::<Vec<_>>();
+
+        if self.numeric {
+            let num =
+                |v: &Option<StrTendril>| v.as_ref().and_then(|v| v.trim().parse::<f64>().ok());
+            keyed.sort_by(|(a, _), (b, _)| cmp_missing_last(num(a), num(b), f64::total_cmp));
+        } else {
+            keyed.sort_by(|(a, _), (b, _)| {
+                cmp_missing_last(a.as_ref(), b.as_ref(), |a, b| a.cmp(b))
+            });
+        }
+
+        keyed.into_iter().map(|(_, n)| n).collect()
+    }
+}
+
+/// Return the value of property in the inline `style` attribute of the element, trimmed. The
+/// attribute is split into declarations by `;`, and each declaration into property and value by the
+/// first `:`. Property names match ignoring ASCII case, and the last declaration wins like CSS.
+/// Comments and escapes are not supported.
+pub(crate) fn inline_style<'a>(e: &'a ElementRef, property: &str) -> Option<&'a str> {
+    e.get_attr(&QualName::new(None, ns!(), local_name!("style")))?
+        .rsplit(';')
+        .filter_map(|d| d.split_once(':'))
+        .find(|(p, _)| p.trim().eq_ignore_ascii_case(property))
+        .map(|(_, v)| v.trim())
+}
+
+/// StyleSelector keeps elements by a property of their inline `style` attribute, dropping Text and
+/// PhantomText nodes. Without value, the property must be declared, otherwise its value must equal
+/// value ignoring ASCII case, like `display: none`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleSelector {
+    property: String,
+    value: Option<String>,
+}
+
+impl StyleSelector {
+    pub fn new(property: &str, value: Option<&str>) -> Self {
+        Self {
+            property: property.to_string(),
+            value: value.map(|v| v.trim().to_string()),
+        }
+    }
+}
+
+impl Selector for StyleSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        std::iter::once(node)
+            .filter(|n| match n {
+                ElementOrTextRef::Element(e) => inline_style(e, &self.property).is_some_and(|v| {
+                    self.value
+                        .as_ref()
+                        .is_none_or(|s| v.eq_ignore_ascii_case(s))
+                }),
+                _ => false,
+            })
+            .collect()
+    }
+}
+
+/// NoAttrSelector keeps Element nodes without attribute name, dropping Text and PhantomText nodes
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoAttrSelector {
+    name: QualName,
+}
+
+impl NoAttrSelector {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+        }
+    }
+}
+
+impl Selector for NoAttrSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        std::iter::once(node)
+            .filter(|n| match n {
+                ElementOrTextRef::Element(e) => e.get_attr(&self.name).is_none(),
+                _ => false,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClassSelector {
     class: String,
     case_sensitive: bool,
@@ -55,7 +196,7 @@ impl ClassSelector {
 }
 
 impl Selector for ClassSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .filter(|n| match n {
                 ElementOrTextRef::Element(e) => e.has_class(&self.class, self.case_sensitive),
@@ -65,7 +206,7 @@ impl Selector for ClassSelector {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IDSelector {
     id: String,
     case_sensitive: bool,
@@ -78,7 +219,7 @@ impl IDSelector {
 }
 
 impl Selector for IDSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .filter(|n| match n {
                 ElementOrTextRef::Element(e) => e.has_id(&self.id, self.case_sensitive),
@@ -88,7 +229,86 @@ impl Selector for IDSelector {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// AttrSplitSelector splits value of attribute name by sep, and yields one PhantomText for each
+/// trimmed, non-empty piece. Text and PhantomText nodes, and elements without the attribute are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrSplitSelector {
+    name: QualName,
+    sep: String,
+}
+
+impl AttrSplitSelector {
+    pub fn new(name: &str, sep: String) -> Self {
+        Self {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            sep,
+        }
+    }
+}
+
+impl Selector for AttrSplitSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match node {
+            ElementOrTextRef::Element(e) => e
+                .get_attr(&self.name)
+                .map(|v| {
+                    v.split(self.sep.as_str())
+                        .map(str::trim)
+                        .filter(|p| !p.is_empty())
+                        .map(|p| ElementOrTextRef::new_phantom_from_txt(StrTendril::from_slice(p)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+}
+
+/// MatchAttrSelector applies the regex to value of attribute name, and yields the capture group of
+/// the first match as PhantomText. Group 0 is the whole match. Nothing is yielded when the regex
+/// does not match or the group does not participate in the match. Text and PhantomText nodes, and
+/// elements without the attribute are dropped.
+#[derive(Debug, Clone)]
+pub struct MatchAttrSelector {
+    name: QualName,
+    regex: Regex,
+    group: usize,
+}
+
+impl MatchAttrSelector {
+    pub fn new(name: &str, regex: Regex, group: usize) -> Self {
+        Self {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            regex,
+            group,
+        }
+    }
+}
+
+impl PartialEq for MatchAttrSelector {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.regex.as_str() == other.regex.as_str()
+            && self.group == other.group
+    }
+}
+
+impl Selector for MatchAttrSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match node {
+            ElementOrTextRef::Element(e) => e
+                .get_attr(&self.name)
+                .and_then(|v| self.regex.captures(v))
+                .and_then(|c| c.get(self.group))
+                .map(|m| ElementOrTextRef::new_phantom_from_txt(StrTendril::from_slice(m.as_str())))
+                .into_iter()
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ExtractAttrSelector {
     attr: QualName,
 }
@@ -102,7 +322,7 @@ impl ExtractAttrSelector {
 }
 
 impl Selector for ExtractAttrSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .filter_map(|n| match n {
                 ElementOrTextRef::Element(e) => e
@@ -113,3 +333,367 @@ impl Selector for ExtractAttrSelector {
             .collect()
     }
 }
+
+/// AttrNamesSelector yields one PhantomText for each attribute name of Element nodes, sorted by
+/// name. With prefix, only names starting with it are kept, ignoring ASCII case. Text and
+/// PhantomText nodes are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrNamesSelector {
+    prefix: Option<String>,
+}
+
+impl AttrNamesSelector {
+    pub fn new(prefix: Option<&str>) -> Self {
+        Self {
+            prefix: prefix.map(str::to_string),
+        }
+    }
+}
+
+impl Selector for AttrNamesSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let ElementOrTextRef::Element(e) = node else {
+            return vec![];
+        };
+
+        let mut names = e
+            .attrs()
+            .map(|(k, _)| &k.local)
+            .filter(|n| match &self.prefix {
+                None => true,
+                Some(p) => n
+                    .as_bytes()
+                    .get(..p.len())
+                    .is_some_and(|b| b.eq_ignore_ascii_case(p.as_bytes())),
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|n| ElementOrTextRef::new_phantom_from_txt(StrTendril::from_slice(n)))
+            .collect()
+    }
+}
+
+/// RoleSelector keeps elements with the ARIA role, given by the first token of the `role`
+/// attribute. With implicit, elements without `role` attribute also match by the implicit role of
+/// some common elements, like `navigation` of `<nav>`. Text and PhantomText nodes are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleSelector {
+    role: String,
+    implicit: bool,
+}
+
+impl RoleSelector {
+    pub fn new(role: &str, implicit: bool) -> Self {
+        Self {
+            role: role.to_ascii_lowercase(),
+            implicit,
+        }
+    }
+
+    /// The implicit role of common elements, simplified from html-aam
+    fn implicit_role(e: &ElementRef) -> Option<&'static str> {
+        let name = e.name()?;
+        if name.ns != ns!(html) {
+            return None;
+        }
+
+        Some(match &*name.local {
+            "a" | "area"
+                if e.get_attr(&QualName::new(None, ns!(), local_name!("href")))
+                    .is_some() =>
+            {
+                "link"
+            }
+            "article" => "article",
+            "aside" => "complementary",
+            "button" => "button",
+            "footer" => "contentinfo",
+            "form" => "form",
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => "heading",
+            "header" => "banner",
+            "img" => "img",
+            "li" => "listitem",
+            "main" => "main",
+            "nav" => "navigation",
+            "ol" | "ul" => "list",
+            "table" => "table",
+            "textarea" => "textbox",
+            _ => return None,
+        })
+    }
+}
+
+impl Selector for RoleSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let matched = match &node {
+            ElementOrTextRef::Element(e) => {
+                match e.get_attr(&QualName::new(None, ns!(), local_name!("role"))) {
+                    Some(role) => role
+                        .split_whitespace()
+                        .next()
+                        .is_some_and(|r| r.eq_ignore_ascii_case(&self.role)),
+                    None => self.implicit && Self::implicit_role(e) == Some(self.role.as_str()),
+                }
+            }
+            _ => false,
+        };
+
+        match matched {
+            true => vec![node],
+            false => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        html::{ElementOrTextRef, Html},
+        querier::Querier,
+    };
+
+    fn query_srcs(hql: &str, doc: &Html) -> Vec<String> {
+        let q = Querier::try_parse(hql).unwrap();
+        q.query_document(doc)
+            .iter()
+            .map(|n| match n {
+                ElementOrTextRef::Element(e) => e
+                    .attrs()
+                    .find(|(k, _)| &k.local == "src")
+                    .unwrap()
+                    .1
+                    .to_string(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_attr_name_case() {
+        let doc = Html::parse_fragment(
+            r#"<img dataValue="a" src="a.png"><svg viewBox="0 0 1 1"><image src="b.png"></image></svg>"#,
+            false,
+        );
+
+        // HTML attribute names are lowercased when parsing, and match ignoring case
+        assert_eq!(
+            query_srcs("@path(`//img`) | @attr(`dataValue`)", &doc),
+            vec!["a.png"]
+        );
+        assert_eq!(
+            query_srcs("@path(`//img`) | @attr(`DATAVALUE`, `a`)", &doc),
+            vec!["a.png"]
+        );
+        assert!(query_srcs("@path(`//img`) | @noAttr(`dataValue`)", &doc).is_empty());
+        let q = Querier::try_parse("@path(`//img`) | #attr(`dataValue`)").unwrap();
+        assert_eq!(
+            Querier::results_to_string(&q.query_document(&doc), ","),
+            "a"
+        );
+
+        // foreign attribute names keep their case
+        let q = Querier::try_parse("@path(`//svg`) | #attr(`viewBox`)").unwrap();
+        assert_eq!(q.query_document(&doc).len(), 1);
+        let q = Querier::try_parse("@path(`//svg`) | #attr(`viewbox`)").unwrap();
+        assert!(q.query_document(&doc).is_empty());
+    }
+
+    #[test]
+    fn test_attr_values() {
+        let doc = Html::parse_fragment(
+            r#"<input type="button" src="a"><input type="SUBMIT" src="b"><input type="text" src="c"><input src="d">"#,
+            false,
+        );
+
+        assert_eq!(
+            query_srcs("@path(`//input`) | @attr(`type`, `button`)", &doc),
+            vec!["a"]
+        );
+        assert_eq!(
+            query_srcs(
+                "@path(`//input`) | @attr(`type`, [`button`, `submit`])",
+                &doc
+            ),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            query_srcs("@path(`//input`) | @attr(`type`, [`text`])", &doc),
+            vec!["c"]
+        );
+        assert!(
+            query_srcs("@path(`//input`) | @attr(`type`, [`reset`, `image`])", &doc).is_empty()
+        );
+        assert_eq!(
+            query_srcs("@path(`//input`) | @attr(`type`)", &doc).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_sort_by_attr() {
+        let doc = Html::parse_fragment(
+            r#"<li data-price="10" src="a"></li><li data-price="9.5" src="b"></li><li src="c"></li><li data-price="n/a" src="d"></li><li data-price=" 100 " src="e"></li>"#,
+            false,
+        );
+
+        // lexical by default, missing attribute last
+        assert_eq!(
+            query_srcs("@path(`//li`) | @sortByAttr(`data-price`)", &doc),
+            vec!["e", "a", "b", "d", "c"]
+        );
+        // non-numeric values last like missing, keeping their order
+        assert_eq!(
+            query_srcs("@path(`//li`) | @sortByAttr(`data-price`, 1)", &doc),
+            vec!["b", "a", "e", "c", "d"]
+        );
+        assert_eq!(
+            query_srcs("@path(`//li`) | @sortByAttr(`data-x`)", &doc),
+            vec!["a", "b", "c", "d", "e"]
+        );
+    }
+
+    #[test]
+    fn test_style() {
+        let doc = Html::parse_fragment(
+            r#"<div style="display:none" src="a"></div><div style=" COLOR : Red ; display: block; display : NONE ;" src="b"></div><div style="color: red" src="c"></div><div src="d"></div><div style="" src="e"></div>"#,
+            false,
+        );
+
+        // presence only
+        assert_eq!(
+            query_srcs("@path(`//div`) | @style(`display`)", &doc),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            query_srcs("@path(`//div`) | @style(`color`)", &doc),
+            vec!["b", "c"]
+        );
+        // value match, the last declaration wins
+        assert_eq!(
+            query_srcs("@path(`//div`) | @style(`display`, `none`)", &doc),
+            vec!["a", "b"]
+        );
+        assert!(query_srcs("@path(`//div`) | @style(`display`, `block`)", &doc).is_empty());
+        assert_eq!(
+            query_srcs("@path(`//div`) | @style(`color`, `red`)", &doc),
+            vec!["b", "c"]
+        );
+        // elements without style
+        assert!(query_srcs("@path(`//div`) | @style(`margin`)", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_attr_split() {
+        let doc = Html::parse_fragment(
+            r#"<img srcset="a.png 1x, b.png 2x,c.png 3x, "><img src="d.png">"#,
+            false,
+        );
+
+        let q = Querier::try_parse("@path(`//img`) | @attrSplit(`srcset`, `,`)").unwrap();
+        assert_eq!(
+            q.query_document(&doc)
+                .iter()
+                .map(|n| n.as_text_cow().to_string())
+                .collect::<Vec<_>>(),
+            vec!["a.png 1x", "b.png 2x", "c.png 3x"]
+        );
+    }
+
+    #[test]
+    fn test_no_attr() {
+        let doc = Html::parse_fragment(
+            r#"<img src="a.png" alt="a">text<img src="b.png"><img src="c.png" alt="">"#,
+            false,
+        );
+
+        assert_eq!(
+            query_srcs("@path(`//img`) | @noAttr(`alt`)", &doc),
+            vec!["b.png"]
+        );
+        assert_eq!(
+            query_srcs("@flat() | @noAttr(`title`) | @attr(`src`)", &doc),
+            vec!["a.png", "b.png", "c.png"]
+        );
+    }
+
+    #[test]
+    fn test_attr_names() {
+        let doc = Html::parse_fragment(
+            r#"<div id="x" data-b="1" class="c" data-a="2" data-="3">text</div><p>no attrs</p>"#,
+            false,
+        );
+
+        let names = |hql: &str| {
+            Querier::try_parse(hql)
+                .unwrap()
+                .query_document(&doc)
+                .iter()
+                .map(|n| n.as_text_cow().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names("@path(`//div`) | @attrNames(`data-`)"),
+            vec!["data-", "data-a", "data-b"]
+        );
+        assert_eq!(
+            names("@path(`//div`) | @attrNames()"),
+            vec!["class", "data-", "data-a", "data-b", "id"]
+        );
+        assert!(names("@path(`//p`) | @attrNames()").is_empty());
+    }
+
+    #[test]
+    fn test_role() {
+        let doc = Html::parse_fragment(
+            r#"<div role="button" src="a"></div><button src="b"></button><span role="Button link" src="c"></span>
+            <nav src="d"></nav><div role="navigation" src="e"></div><nav role="menu" src="f"></nav>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_srcs("@flat() | @role(`button`)", &doc),
+            vec!["a", "c"]
+        );
+        assert_eq!(
+            query_srcs("@flat() | @role(`button`, 1)", &doc),
+            vec!["a", "b", "c"]
+        );
+        // an explicit role overrides the implicit one
+        assert_eq!(
+            query_srcs("@flat() | @role(`navigation`, 1)", &doc),
+            vec!["d", "e"]
+        );
+    }
+
+    #[test]
+    fn test_match_attr() {
+        let doc = Html::parse_fragment(
+            r#"<a href="/item/42?x=1">a</a><a href="/about">b</a><a>c</a><a href="/item/7/item/8">d</a>"#,
+            false,
+        );
+        let texts = |hql| {
+            Querier::try_parse(hql)
+                .unwrap()
+                .query_document(&doc)
+                .iter()
+                .map(|n| n.as_text_cow().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts(r"@path(`//a`) | @matchAttr(`href`, `/item/(\d+)`, 1)"),
+            vec!["42", "7"]
+        );
+        assert_eq!(
+            texts(r"@path(`//a`) | @matchAttr(`href`, `/item/(\d+)`, 0)"),
+            vec!["/item/42", "/item/7"]
+        );
+        // the group does not exist
+        assert!(texts(r"@path(`//a`) | @matchAttr(`href`, `/item/(\d+)`, 2)").is_empty());
+        assert!(texts(r"@path(`//a`) | @matchAttr(`href`, `^/contact`, 0)").is_empty());
+    }
+}
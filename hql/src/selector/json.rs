@@ -0,0 +1,102 @@
+use html5ever::tendril::StrTendril;
+use serde_json::Value;
+
+use crate::html::ElementOrTextRef;
+
+use super::Selector;
+
+/// One step of a JSON path, `.key` or `[index]`
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// JsonPathSelector parses the text of each node as JSON and resolves a minimal JSONPath like
+/// `$.offers[0].price` on it. The resolved value is yielded as a PhantomText, with strings
+/// unquoted and other values serialized as JSON. Nodes whose text is not valid JSON or does not
+/// contain the path are dropped. Elements are parsed by their text, like `<script>` contents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPathSelector {
+    path: Vec<JsonPathSegment>,
+}
+
+impl JsonPathSelector {
+    pub fn new(path: Vec<JsonPathSegment>) -> Self {
+        Self { path }
+    }
+
+    fn resolve<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.path.iter().try_fold(value, |v, seg| match seg {
+            JsonPathSegment::Key(k) => v.get(k.as_str()),
+            JsonPathSegment::Index(i) => v.get(*i),
+        })
+    }
+}
+
+impl Selector for JsonPathSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let text = match &node {
+            ElementOrTextRef::Element(e) => e.text_content(),
+            ElementOrTextRef::PhantomElement(e) => e.element().text_content(),
+            ElementOrTextRef::Text(t) => t.text().text().clone(),
+            ElementOrTextRef::PhantomText(t) => t.text().text().clone(),
+        };
+
+        let Ok(value) = serde_json::from_str::<Value>(&text) else {
+            return vec![];
+        };
+
+        self.resolve(&value)
+            .map(|v| {
+                let txt = match v {
+                    Value::String(s) => StrTendril::from_slice(s),
+                    _ => StrTendril::from(v.to_string()),
+                };
+                ElementOrTextRef::new_phantom_from_txt(txt)
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{html::Html, querier::Querier};
+
+    fn query_json(hql: &str, doc: &Html) -> Vec<String> {
+        let q = Querier::try_parse(hql).unwrap();
+        q.query_document(doc)
+            .iter()
+            .map(|n| n.as_text_cow().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_json_path() {
+        let doc = Html::parse_fragment(
+            r#"<script type="application/ld+json">
+                {"name": "a", "offers": {"price": 9.5, "currency": "USD"}, "tags": ["x", "y"]}
+            </script><script>not json</script>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_json("@path(`//script`) | #json(`$.offers.price`)", &doc),
+            vec!["9.5"]
+        );
+        assert_eq!(
+            query_json("@path(`//script`) | #json(`$.tags[1]`)", &doc),
+            vec!["y"]
+        );
+        assert_eq!(
+            query_json(
+                "@path(`//script`) | #json(`$.offers`) | #json(`$.currency`)",
+                &doc
+            ),
+            vec!["USD"]
+        );
+        assert!(query_json("@path(`//script`) | #json(`$.tags[2]`)", &doc).is_empty());
+        assert!(query_json("@path(`//script`) | #json(`$.name.x`)", &doc).is_empty());
+    }
+}
@@ -3,7 +3,8 @@
 //! The Selector is a trait which accepts a `ElementOrTextRef` node and returns
 //! a vector of ElementOrTextRef nodes. And for many selector implementations, Use
 //! [enum_dispatch](https://docs.rs/enum_dispatch) trick instead of dynamic dispatch
-//! for better performance.
+//! for better performance. Some batch-level selectors, like `#index()`, work on the whole node set
+//! generated by the previous selector instead of on each node.
 //!
 //! Each of the submodule contains a group of selector implementations, like selecting
 //! by path like xpath or selecting by classes like css selector.
@@ -17,23 +18,41 @@
 //! ## Manually
 //!
 //! ```
+//! use hql::prelude::*;
+//!
 //! let selectors: Vec<SelectorEnum> = vec![
 //!     PathSelector::new(vec![(Path::Travel, "div".into()), (Path::Single, "a".into())]).into(),
 //!     FlatSelector::new().into(),
-//! ]
+//! ];
+//!
+//! let doc = Html::parse_fragment(r#"<div><a href="/">home</a></div>"#, false);
+//! let nodes = Querier::new(selectors).query_document(&doc).into_iter().collect::<String>();
+//! assert_eq!(nodes, r#"<a href="/">home</a>home"#);
 //! ```
 //!
 //! ## Parse HQL
 //!
 //! ```
+//! # use hql::selector::{path::{FlatSelector, Path, PathSelector}, try_parse_hql, SelectorEnum};
 //! let selectors: Vec<SelectorEnum> =
 //!     try_parse_hql("@path(`//div/a`) | @flat()").unwrap_or_else(|e| panic!("{}", e));
+//!
+//! assert_eq!(
+//!     selectors,
+//!     vec![
+//!         PathSelector::new(vec![(Path::Travel, "div".into()), (Path::Single, "a".into())]).into(),
+//!         FlatSelector::new().into(),
+//!     ]
+//! );
 //! ```
 //!
 //! The full HQL grammar is define in [grammar.pest](https://github.com/xylonx/hql/tree/master/src/selector/grammar.pest)
 
 pub mod attr;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod path;
+pub mod sibling;
 pub mod text;
 
 use enum_dispatch::enum_dispatch;
@@ -45,31 +64,129 @@ use pest_derive::Parser;
 
 use crate::html::ElementOrTextRef;
 
-use self::{attr::*, path::*, text::*};
+#[cfg(feature = "serde")]
+use self::json::*;
+use self::{attr::*, path::*, sibling::*, text::*};
 
 #[enum_dispatch]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SelectorEnum {
     PathSelector,
 
     AttrSelector,
+    NoAttrSelector,
+    SortByAttrSelector,
+    StyleSelector,
+    AttrNamesSelector,
+    RoleSelector,
     ClassSelector,
     IDSelector,
 
     FlatSelector,
+    ExcludeSelector,
+    TemplateContentSelector,
+    WrapSelector,
+    SampleSelector,
+    TakeUntilSelector,
+    DropUntilSelector,
+    DepthSelector,
+
+    AdjacentSelector,
+    PrevTextSelector,
+    FollowingSelector,
+    BetweenSelector,
+    LastPerParentSelector,
+    DescendantOfSelector,
+    AnySelector,
+    MinChildrenSelector,
+    MaxChildrenSelector,
+
+    HasTextSelector,
+    VisibleTextSelector,
+    TitleSelector,
+    PiSelector,
+    EqSelector,
 
     TextSelector,
+    TextWithBreaksSelector,
+    OwnTextSelector,
+    TextLenSelector,
+    TextAllSelector,
+    LeavesSelector,
     TrimSelector,
+    TrimCharsSelector,
+    CapitalizeSelector,
+    UnixNewlinesSelector,
+    ResolveUrlSelector,
+    TitleCaseSelector,
     TrimPrefixSelector,
     TrimSuffixSelector,
+    ReplaceLiteralSelector,
+    TextSliceSelector,
+    ConstSelector,
+    RepeatSelector,
+    IndexSelector,
+    UniqueTextSelector,
+
+    #[cfg(feature = "serde")]
+    JsonPathSelector,
+    #[cfg(feature = "ascii")]
+    AsciiFoldSelector,
     NthChildSelector,
+    ChildrenSelector,
     ExtractAttrSelector,
+    TagNameSelector,
+    AttrSplitSelector,
+    MatchAttrSelector,
+}
+
+/// Whether selectors evaluated from node yield anything, which is how selectors taking nested
+/// selectors as predicates, like `@between()`, match nodes
+pub(crate) fn yields_any(selectors: &[SelectorEnum], node: ElementOrTextRef<'_>) -> bool {
+    let mut nodes = vec![node];
+    for s in selectors {
+        nodes = s.select_batch(nodes);
+        if nodes.is_empty() {
+            return false;
+        }
+    }
+    true
 }
 
 #[enum_dispatch(SelectorEnum)]
 pub trait Selector: PartialEq {
     /// TODO(xylonx): use iterator tricks instead of Vec here to avoid intermediate memory consumption
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>>;
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>>;
+
+    /// Select on the whole node set generated by the previous selector.
+    ///
+    /// By default, each node is selected independently by `select` and the results are concatenated.
+    /// Batch-level selectors, whose output depends on the whole set like `#index()`, override it.
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        nodes.into_iter().flat_map(|n| self.select(n)).collect()
+    }
+
+    /// Whether the selector overrides `select_batch` and needs the whole node set
+    fn is_batch(&self) -> bool {
+        false
+    }
+
+    /// Name of the selector type, like `PathSelector`, for introspecting parsed HQL
+    fn kind_name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Select lazily, so that consumers only interested in some results can stop early.
+    ///
+    /// By default, it just iterates over results of `select`. Generating selectors like `@flat()`
+    /// override it to avoid traversing more than needed.
+    fn select_iter<'a: 'i, 'b: 'i, 'i>(
+        &'b self,
+        node: ElementOrTextRef<'a>,
+    ) -> Box<dyn Iterator<Item = ElementOrTextRef<'a>> + 'i> {
+        Box::new(self.select(node).into_iter())
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -77,22 +194,56 @@ pub trait Selector: PartialEq {
 struct HqlParser;
 
 impl HqlParser {
-    fn parse_path(pair: Pair<'_, Rule>) -> (Path, String) {
+    fn parse_path(pair: Pair<'_, Rule>) -> (Path, PathStep) {
         let mut pairs = pair.into_inner();
 
-        let p_node = match pairs.next().unwrap().as_rule() {
-            Rule::singlePath => Path::Single,
-            Rule::travelPath => Path::Travel,
-            _ => unreachable!(),
-        };
-
-        let t = pairs.next().unwrap();
-        let tag = match t.as_rule() {
-            Rule::tag => t.as_str().to_string(),
-            _ => unreachable!(),
+        // an axis overrides the slash before it
+        let mut p_node = Path::Single;
+        let step = loop {
+            let p = pairs.next().unwrap();
+            match p.as_rule() {
+                Rule::singlePath => p_node = Path::Single,
+                Rule::travelPath => p_node = Path::Travel,
+                Rule::axis => {
+                    p_node = match p.as_str() {
+                        "self::" => Path::Current,
+                        "child::" => Path::Single,
+                        "descendant::" => Path::Travel,
+                        _ => unreachable!(),
+                    }
+                }
+                Rule::step => break p,
+                _ => unreachable!(),
+            }
         };
 
-        (p_node, tag)
+        let step = step
+            .into_inner()
+            .fold(PathStep::default(), |step, q| match q.as_rule() {
+                Rule::nsPrefix => step.with_ns(match q.as_str() {
+                    "html" => ns!(html),
+                    "svg" => ns!(svg),
+                    "math" => ns!(mathml),
+                    _ => unreachable!(),
+                }),
+                Rule::tag => step.with_tag(q.as_str().to_string()),
+                // the regex is validated in try_parse_hql
+                Rule::tagRegex => step.with_tag_regex(Self::raw_text(q)).unwrap(),
+                Rule::classQualifier => {
+                    step.with_class(q.into_inner().next().unwrap().as_str().to_string())
+                }
+                Rule::idQualifier => {
+                    step.with_id(q.into_inner().next().unwrap().as_str().to_string())
+                }
+                Rule::lastPredicate => step.with_from_last(
+                    q.into_inner()
+                        .next()
+                        .map_or(0, |k| k.as_str().parse().unwrap()),
+                ),
+                _ => unreachable!(),
+            });
+
+        (p_node, step)
     }
 
     // quotedPath
@@ -110,6 +261,42 @@ impl HqlParser {
         // .into()
     }
 
+    /// Decode escape sequences of text. Unknown escape sequences are kept as is
+    fn unescape(text: &str) -> String {
+        let mut s = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                s.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => s.push('\n'),
+                Some('t') => s.push('\t'),
+                Some('r') => s.push('\r'),
+                Some('\\') => s.push('\\'),
+                Some('`') => s.push('`'),
+                Some(c) => {
+                    s.push('\\');
+                    s.push(c);
+                }
+                None => s.push('\\'),
+            }
+        }
+        s
+    }
+
+    // quotedText
+    fn parse_text(pair: Pair<'_, Rule>) -> String {
+        Self::unescape(Self::raw_text(pair))
+    }
+
+    // quotedText or tagRegex as written, for regexes which decode escapes themselves
+    fn raw_text(pair: Pair<'_, Rule>) -> &str {
+        pair.into_inner().next().unwrap().as_str()
+    }
+
     fn parse_attr(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
         let name = pairs.next().unwrap().into_inner().next().unwrap();
         let name_str = match name.as_rule() {
@@ -118,13 +305,30 @@ impl HqlParser {
         };
 
         match pairs.next() {
-            Some(v) => {
-                AttrSelector::new(&name_str, Some(v.into_inner().next().unwrap().as_str())).into()
+            Some(v) if v.as_rule() == Rule::attrValues => {
+                let vals = v.into_inner().map(Self::parse_text).collect::<Vec<_>>();
+                AttrSelector::new_any_of(&name_str, Some(vals.iter().map(|v| v.as_str()).collect()))
+                    .into()
             }
+            Some(v) => AttrSelector::new(&name_str, Some(&Self::parse_text(v))).into(),
             None => AttrSelector::new(&name_str, None).into(),
         }
     }
 
+    fn parse_match_attr(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
+        let name = pairs.next().unwrap().into_inner().next().unwrap().as_str();
+        // the regex is validated in try_parse_hql
+        let regex = regex::Regex::new(Self::raw_text(pairs.next().unwrap())).unwrap();
+        let group = pairs.next().unwrap().as_str().parse().unwrap();
+        MatchAttrSelector::new(name, regex, group).into()
+    }
+
+    fn parse_attr_split(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
+        let name = pairs.next().unwrap().into_inner().next().unwrap().as_str();
+        let sep = Self::parse_text(pairs.next().unwrap());
+        AttrSplitSelector::new(name, sep).into()
+    }
+
     /// parse pairs into IDSelector, with case sensitive as default
     fn parse_id(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
         let id = pairs.next().unwrap().into_inner().next().unwrap();
@@ -144,6 +348,23 @@ impl HqlParser {
         IDSelector::new(id_str, true).into()
     }
 
+    /// parse pairs into EqSelector, not trimming and case sensitive as default
+    fn parse_eq(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
+        let value = Self::parse_text(pairs.next().unwrap());
+
+        let mut trim = false;
+        let mut case_sensitive = true;
+        for opt in pairs {
+            match opt.as_rule() {
+                Rule::trimOpt => trim = opt.as_str() == "1",
+                Rule::caseSensitiveOpt => case_sensitive = opt.as_str() == "1",
+                _ => unreachable!(),
+            }
+        }
+
+        EqSelector::new(value, trim, case_sensitive).into()
+    }
+
     /// parse pairs into ClassSelector, with case sensitive as default
     fn parse_class(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
         let class = pairs.next().unwrap().into_inner().next().unwrap();
@@ -163,10 +384,17 @@ impl HqlParser {
         ClassSelector::new(class_str, true).into()
     }
 
+    fn parse_role(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
+        let role = pairs.next().unwrap().into_inner().next().unwrap().as_str();
+        let implicit = pairs.next().is_some_and(|p| p.as_str() == "1");
+        RoleSelector::new(role, implicit).into()
+    }
+
     fn parse_child(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
         let n_str = pairs.next().unwrap().as_str();
 
-        // grammar ensures n_str contains at least one characters
+        // grammar ensures n_str contains at least one characters, and check_expr ensures it fits
+        // in usize
         let (neg_sign, n) = match &n_str[0..=0] {
             "-" => (true, n_str[1..=n_str.len() - 1].parse::<usize>().unwrap()),
             _ => (false, n_str.parse::<usize>().unwrap()),
@@ -178,52 +406,287 @@ impl HqlParser {
         NthChildSelector::new(n, false).into()
     }
 
+    fn parse_slice(mut pairs: Pairs<'_, Rule>) -> SelectorEnum {
+        let start = pairs.next().unwrap().as_str().parse::<isize>().unwrap();
+        let end = pairs.next().unwrap().as_str().parse::<isize>().unwrap();
+        TextSliceSelector::new(start, end).into()
+    }
+
     fn parse_expr(pair: Pair<'_, Rule>) -> SelectorEnum {
         match pair.as_rule() {
             Rule::childExpr => Self::parse_child(pair.into_inner()),
+            Rule::childrenExpr => ChildrenSelector::new().into(),
             Rule::flatExpr => FlatSelector::new().into(),
+            Rule::templateContentExpr => TemplateContentSelector::new().into(),
+            Rule::visibleExpr => VisibleTextSelector::new().into(),
+            Rule::titleExpr => TitleSelector::new().into(),
+            Rule::piExpr => PiSelector::new(pair.into_inner().next().map(Self::parse_text)).into(),
+            Rule::hasTextExpr => {
+                HasTextSelector::new(pair.into_inner().next().map(Self::parse_text)).into()
+            }
+            Rule::excludeExpr => ExcludeSelector::new(
+                pair.into_inner()
+                    .map(|t| t.into_inner().next().unwrap().as_str().to_string())
+                    .collect(),
+            )
+            .into(),
+            Rule::descendantOfExpr => DescendantOfSelector::new(Self::parse_stmt(
+                pair.into_inner().next().unwrap().into_inner(),
+            ))
+            .into(),
+            Rule::takeUntilExpr => TakeUntilSelector::new(Self::parse_stmt(
+                pair.into_inner().next().unwrap().into_inner(),
+            ))
+            .into(),
+            Rule::dropUntilExpr => DropUntilSelector::new(Self::parse_stmt(
+                pair.into_inner().next().unwrap().into_inner(),
+            ))
+            .into(),
+            Rule::anyExpr => AnySelector::new(
+                pair.into_inner()
+                    .map(|p| Self::parse_stmt(p.into_inner()))
+                    .collect(),
+            )
+            .into(),
+            Rule::betweenExpr => {
+                let mut pipelines = pair.into_inner().map(|p| Self::parse_stmt(p.into_inner()));
+                BetweenSelector::new(pipelines.next().unwrap(), pipelines.next().unwrap()).into()
+            }
+            Rule::lastPerParentExpr => LastPerParentSelector::new().into(),
+            Rule::prevTextExpr => PrevTextSelector::new().into(),
+            Rule::adjacentExpr => AdjacentSelector::new(
+                pair.into_inner()
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .next()
+                    .unwrap()
+                    .as_str(),
+            )
+            .into(),
+            Rule::followingExpr => FollowingSelector::new(
+                pair.into_inner()
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .next()
+                    .unwrap()
+                    .as_str(),
+            )
+            .into(),
+            Rule::wrapExpr => WrapSelector::new(
+                pair.into_inner()
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .next()
+                    .unwrap()
+                    .as_str(),
+            )
+            .into(),
             Rule::pathExpr => Self::parse_paths(pair.into_inner()),
             Rule::attrExpr => Self::parse_attr(pair.into_inner()),
+            Rule::noAttrExpr => NoAttrSelector::new(
+                pair.into_inner()
+                    .next()
+                    .unwrap()
+                    .into_inner()
+                    .next()
+                    .unwrap()
+                    .as_str(),
+            )
+            .into(),
+            Rule::attrNamesExpr => AttrNamesSelector::new(
+                pair.into_inner()
+                    .next()
+                    .map(|p| p.into_inner().next().unwrap().as_str()),
+            )
+            .into(),
+            Rule::attrSplitExpr => Self::parse_attr_split(pair.into_inner()),
+            Rule::styleExpr => {
+                let mut pairs = pair.into_inner();
+                let property = pairs.next().unwrap().into_inner().next().unwrap();
+                let value = pairs.next().map(Self::parse_text);
+                StyleSelector::new(property.as_str(), value.as_deref()).into()
+            }
+            Rule::sortByAttrExpr => {
+                let mut pairs = pair.into_inner();
+                let name = pairs.next().unwrap().into_inner().next().unwrap();
+                let numeric = pairs.next().is_some_and(|opt| opt.as_str() == "1");
+                SortByAttrSelector::new(name.as_str(), numeric).into()
+            }
+            Rule::matchAttrExpr => Self::parse_match_attr(pair.into_inner()),
             Rule::idExpr => Self::parse_id(pair.into_inner()),
             Rule::classExpr => Self::parse_class(pair.into_inner()),
-            Rule::textExpr => TextSelector::new().into(),
+            Rule::roleExpr => Self::parse_role(pair.into_inner()),
+            Rule::textExpr => {
+                let mut texts = pair.into_inner().map(Self::parse_text);
+                TextSelector::new(texts.next(), texts.next()).into()
+            }
+            Rule::textWithBreaksExpr => TextWithBreaksSelector::new().into(),
+            Rule::ownTextExpr => OwnTextSelector::new().into(),
+            Rule::textLenExpr => TextLenSelector::new().into(),
+            Rule::leavesExpr => LeavesSelector::new().into(),
+            Rule::textAllExpr => TextAllSelector::new(
+                pair.into_inner()
+                    .next()
+                    .map(Self::parse_text)
+                    .unwrap_or_default(),
+            )
+            .into(),
             Rule::trimExpr => TrimSelector::new().into(),
-            Rule::trimPrefixExpr => TrimPrefixSelector::new(
+            #[cfg(feature = "ascii")]
+            Rule::asciiExpr => AsciiFoldSelector::new().into(),
+            Rule::trimCharsExpr => {
+                TrimCharsSelector::new(Self::parse_text(pair.into_inner().next().unwrap())).into()
+            }
+            Rule::unixNewlinesExpr => UnixNewlinesSelector::new().into(),
+            Rule::capitalizeExpr => CapitalizeSelector::new().into(),
+            Rule::titleCaseExpr => TitleCaseSelector::new().into(),
+            Rule::trimPrefixExpr => {
+                TrimPrefixSelector::new(Self::parse_text(pair.into_inner().next().unwrap())).into()
+            }
+            Rule::trimSuffixExpr => {
+                TrimSuffixSelector::new(Self::parse_text(pair.into_inner().next().unwrap())).into()
+            }
+            Rule::extractAttrExpr => ExtractAttrSelector::new(
                 pair.into_inner()
                     .next()
                     .unwrap()
                     .into_inner()
+                    .next()
+                    .unwrap()
+                    .as_str(),
+            )
+            .into(),
+            Rule::resolveUrlExpr => ResolveUrlSelector::new(
+                // the base is validated in try_parse_hql
+                url::Url::parse(&Self::parse_text(pair.into_inner().next().unwrap())).unwrap(),
+            )
+            .into(),
+            Rule::replaceLiteralExpr => {
+                let mut texts = pair.into_inner().map(Self::parse_text);
+                ReplaceLiteralSelector::new(texts.next().unwrap(), texts.next().unwrap()).into()
+            }
+            Rule::eqExpr => Self::parse_eq(pair.into_inner()),
+            Rule::sliceExpr => Self::parse_slice(pair.into_inner()),
+            Rule::uniqueTextExpr => UniqueTextSelector::new().into(),
+            Rule::indexExpr => IndexSelector::new().into(),
+            Rule::tagNameExpr => TagNameSelector::new().into(),
+            Rule::constExpr => {
+                ConstSelector::new(&Self::parse_text(pair.into_inner().next().unwrap())).into()
+            }
+            Rule::minChildrenExpr => MinChildrenSelector::new(
+                pair.into_inner()
                     .next()
                     .unwrap()
                     .as_str()
-                    .to_string(),
+                    .parse::<usize>()
+                    .unwrap(),
             )
             .into(),
-            Rule::trimSuffixExpr => TrimSuffixSelector::new(
+            Rule::maxChildrenExpr => MaxChildrenSelector::new(
                 pair.into_inner()
                     .next()
                     .unwrap()
-                    .into_inner()
+                    .as_str()
+                    .parse::<usize>()
+                    .unwrap(),
+            )
+            .into(),
+            #[cfg(feature = "serde")]
+            Rule::jsonExpr => Self::parse_json_path(pair.into_inner().next().unwrap()),
+            Rule::depthExpr => DepthSelector::new(
+                pair.into_inner()
                     .next()
                     .unwrap()
                     .as_str()
-                    .to_string(),
+                    .parse::<usize>()
+                    .unwrap(),
             )
             .into(),
-            Rule::extractAttrExpr => ExtractAttrSelector::new(
+            Rule::sampleExpr => SampleSelector::new(
                 pair.into_inner()
                     .next()
                     .unwrap()
-                    .into_inner()
+                    .as_str()
+                    .parse::<usize>()
+                    .unwrap(),
+            )
+            .into(),
+            Rule::repeatExpr => RepeatSelector::new(
+                pair.into_inner()
                     .next()
                     .unwrap()
-                    .as_str(),
+                    .as_str()
+                    .parse::<usize>()
+                    .unwrap(),
             )
             .into(),
             _ => unreachable!(),
         }
     }
 
+    #[cfg(feature = "serde")]
+    fn parse_json_path(pair: Pair<'_, Rule>) -> SelectorEnum {
+        let segments = pair
+            .into_inner()
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|seg| match seg.as_rule() {
+                Rule::jsonKey => JsonPathSegment::Key(seg.as_str().to_string()),
+                Rule::jsonIndex => JsonPathSegment::Index(seg.as_str().parse().unwrap()),
+                _ => unreachable!(),
+            })
+            .collect();
+        JsonPathSelector::new(segments).into()
+    }
+
+    /// check arguments which are valid in grammar but rejected when building selectors, returning
+    /// the error message. Parsing functions can unwrap them afterwards.
+    fn check_expr(pair: &Pair<'_, Rule>) -> Option<String> {
+        match pair.as_rule() {
+            // numbers are parsed as usize, and negative ones are parsed without the sign
+            Rule::posNumber | Rule::nonZeroNumber | Rule::jsonIndex | Rule::negNumber => {
+                let digits = pair.as_str().trim_start_matches('-');
+                digits.parse::<usize>().err().map(|_| {
+                    format!(
+                        "number {} is out of range, at most {}",
+                        pair.as_str(),
+                        usize::MAX
+                    )
+                })
+            }
+            Rule::sliceExpr => pair.clone().into_inner().find_map(|n| {
+                n.as_str().parse::<isize>().err().map(|_| {
+                    format!(
+                        "slice index {} is out of range, expecting {}..={}",
+                        n.as_str(),
+                        isize::MIN,
+                        isize::MAX
+                    )
+                })
+            }),
+            Rule::resolveUrlExpr => {
+                let base = Self::parse_text(pair.clone().into_inner().next().unwrap());
+                url::Url::parse(&base)
+                    .err()
+                    .map(|e| format!("invalid base url of #resolveUrl(): {e}"))
+            }
+            Rule::tagRegex => PathStep::default()
+                .with_tag_regex(Self::raw_text(pair.clone()))
+                .err()
+                .map(|e| format!("invalid regex of path step: {e}")),
+            Rule::matchAttrExpr => {
+                regex::Regex::new(Self::raw_text(pair.clone().into_inner().nth(1).unwrap()))
+                    .err()
+                    .map(|e| format!("invalid regex of @matchAttr(): {e}"))
+            }
+            _ => None,
+        }
+    }
+
     fn parse_stmt(pairs: Pairs<'_, Rule>) -> Vec<SelectorEnum> {
         pairs
             .into_iter()
@@ -235,13 +698,16 @@ impl HqlParser {
     }
 }
 
+/// Error of parsing HQL, returned by `try_parse_hql` and the functions built on it
+pub type HqlError = pest::error::Error<Rule>;
+
 /// Parse input as hql defined in [grammar.pest](https://github.com/xylonx/hql/tree/master/src/selector/grammar.pest)
 /// and return a series of Selectors.
 ///
 /// Throw pest::error::Error when input does not follow the grammar. It implements Display trait with
 /// more readable error like below
 ///
-/// ```
+/// ```text
 /// --> 1:1
 /// |
 /// 1 | #child(2)
@@ -249,9 +715,68 @@ impl HqlParser {
 /// |
 /// = expected flatExpr, pathExpr, attrExpr, idExpr, classExpr, or helperExpr
 /// ```
+///
+/// ```
+/// # use hql::selector::try_parse_hql;
+/// let err = try_parse_hql("#child(2)").unwrap_err();
+/// assert!(err.to_string().contains("1 | #child(2)"));
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn try_parse_hql(input: &str) -> Result<Vec<SelectorEnum>, HqlError> {
+    try_parse_rule(Rule::hql, input)
+}
+
+/// Parse input as a single selector expression, i.e. one stage of a pipeline without `|`
 #[allow(clippy::result_large_err)]
-pub fn try_parse_hql(input: &str) -> Result<Vec<SelectorEnum>, pest::error::Error<Rule>> {
-    Ok(HqlParser::parse_stmt(HqlParser::parse(Rule::hql, input)?))
+pub fn try_parse_stage(input: &str) -> Result<SelectorEnum, HqlError> {
+    // the grammar ensures exactly one selector
+    Ok(try_parse_rule(Rule::stage, input)?.pop().unwrap())
+}
+
+#[allow(clippy::result_large_err)]
+fn try_parse_rule(rule: Rule, input: &str) -> Result<Vec<SelectorEnum>, HqlError> {
+    let pairs = HqlParser::parse(rule, input)?;
+
+    // the grammar is shared by all features, so reject selectors which are not compiled in here
+    #[cfg(not(feature = "serde"))]
+    if let Some(p) = pairs
+        .clone()
+        .flatten()
+        .find(|p| p.as_rule() == Rule::jsonExpr)
+    {
+        return Err(pest::error::Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: "#json() requires the serde feature".to_string(),
+            },
+            p.as_span(),
+        ));
+    }
+    #[cfg(not(feature = "ascii"))]
+    if let Some(p) = pairs
+        .clone()
+        .flatten()
+        .find(|p| p.as_rule() == Rule::asciiExpr)
+    {
+        return Err(pest::error::Error::new_from_span(
+            pest::error::ErrorVariant::CustomError {
+                message: "#ascii() requires the ascii feature".to_string(),
+            },
+            p.as_span(),
+        ));
+    }
+
+    if let Some((p, message)) = pairs
+        .clone()
+        .flatten()
+        .find_map(|p| HqlParser::check_expr(&p).map(|m| (p, m)))
+    {
+        return Err(pest::error::Error::new_from_span(
+            pest::error::ErrorVariant::CustomError { message },
+            p.as_span(),
+        ));
+    }
+
+    Ok(HqlParser::parse_stmt(pairs))
 }
 
 #[cfg(test)]
@@ -265,9 +790,56 @@ mod test {
             ("@flat()", vec![FlatSelector::new().into()]),
 
             ("@path(`/body//div/a`)", vec![PathSelector::new(vec![(Path::Single, "body".into()), (Path::Travel, "div".into()), (Path::Single, "a".into())]).into()]),
+            ("@path(`//svg|a`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::from("a").with_ns(ns!(svg)))]).into()]),
+            ("@path(`//div.content`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::from("div").with_class("content".into()))]).into()]),
+            ("@path(`//#main`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::new(None).with_id("main".into()))]).into()]),
+            ("@path(`//div.content.wide#main`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::from("div").with_class("content".into()).with_class("wide".into()).with_id("main".into()))]).into()]),
+            ("@path(`//ul/li.x[last()-2]`)", vec![PathSelector::new(vec![(Path::Travel, "ul".into()), (Path::Single, PathStep::from("li").with_class("x".into()).with_from_last(2))]).into()]),
+            ("@path(`//li[last()]`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::from("li").with_from_last(0))]).into()]),
+            ("@path(`self::div`)", vec![PathSelector::new(vec![(Path::Current, "div".into())]).into()]),
+            ("@path(`child::a`)", vec![PathSelector::new(vec![(Path::Single, "a".into())]).into()]),
+            ("@path(`descendant::span.x`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::from("span").with_class("x".into()))]).into()]),
+            ("@path(`self::div/child::ul//li/descendant::a`)", vec![PathSelector::new(vec![(Path::Current, "div".into()), (Path::Single, "ul".into()), (Path::Travel, "li".into()), (Path::Travel, "a".into())]).into()]),
+            ("@path(`/child`)", vec![PathSelector::new(vec![(Path::Single, "child".into())]).into()]),
+            (r"@path(`//~`section-\d+`/p`)", vec![PathSelector::new(vec![(Path::Travel, PathStep::default().with_tag_regex(r"section-\d+").unwrap()), (Path::Single, "p".into())]).into()]),
+            ("@path(`/svg|~`[a-z]+`.x`)", vec![PathSelector::new(vec![(Path::Single, PathStep::default().with_ns(ns!(svg)).with_tag_regex("[a-z]+").unwrap().with_class("x".into()))]).into()]),
+
+            ("@templateContent()", vec![TemplateContentSelector::new().into()]),
+            ("@exclude(`script`)", vec![ExcludeSelector::new(vec!["script".into()]).into()]),
+            ("@exclude(`script`, `style`)", vec![ExcludeSelector::new(vec!["script".into(), "style".into()]).into()]),
+            ("@wrap(`section`)", vec![WrapSelector::new("section").into()]),
+            ("@adjacent(`dd`)", vec![AdjacentSelector::new("dd").into()]),
+            ("@following(`li`)", vec![FollowingSelector::new("li").into()]),
+            ("@lastPerParent()", vec![LastPerParentSelector::new().into()]),
+            ("@prevText()", vec![PrevTextSelector::new().into()]),
+            ("@between(@class(`a`), #tagName() | #eq(`h2`))", vec![BetweenSelector::new(vec![ClassSelector::new("a".into(), true).into()], vec![TagNameSelector::new().into(), EqSelector::new("h2".into(), false, true).into()]).into()]),
+            ("@path(`//h2/x-1`)", vec![PathSelector::new(vec![(Path::Travel, "h2".into()), (Path::Single, "x-1".into())]).into()]),
+            ("@descendantOf(@class(`menu`) | @hasText())", vec![DescendantOfSelector::new(vec![ClassSelector::new("menu".into(), true).into(), HasTextSelector::new(None).into()]).into()]),
+            ("@minChildren(3)", vec![MinChildrenSelector::new(3).into()]),
+            ("@any(@path(`//a`), @path(`//button`) | @attr(`type`))", vec![AnySelector::new(vec![vec![PathSelector::new(vec![(Path::Travel, "a".into())]).into()], vec![PathSelector::new(vec![(Path::Travel, "button".into())]).into(), AttrSelector::new("type", None).into()]]).into()]),
+            ("@maxChildren(0)", vec![MaxChildrenSelector::new(0).into()]),
 
             ("@attr(`target`, `_blank`)", vec![AttrSelector::new("target", Some("_blank")).into()]),
             ("@attr(`href`)", vec![AttrSelector::new("href", None).into()]),
+            ("@attr(`type`, [`button`, `submit`])", vec![AttrSelector::new_any_of("type", Some(vec!["button", "submit"])).into()]),
+            ("@attr(`type`, [`button`])", vec![AttrSelector::new("type", Some("button")).into()]),
+            ("@attrSplit(`srcset`, `,`)", vec![AttrSplitSelector::new("srcset", ",".into()).into()]),
+            (
+                r"@matchAttr(`href`, `/item/(\d+)`, 1)",
+                vec![MatchAttrSelector::new("href", regex::Regex::new(r"/item/(\d+)").unwrap(), 1).into()],
+            ),
+            ("@noAttr(`alt`)", vec![NoAttrSelector::new("alt").into()]),
+            ("@style(`display`)", vec![StyleSelector::new("display", None).into()]),
+            ("@style(`display`, `none`)", vec![StyleSelector::new("display", Some("none")).into()]),
+            ("@sortByAttr(`data-price`)", vec![SortByAttrSelector::new("data-price", false).into()]),
+            ("@sortByAttr(`data-price`, 1)", vec![SortByAttrSelector::new("data-price", true).into()]),
+            ("@attrNames()", vec![AttrNamesSelector::new(None).into()]),
+            ("@role(`button`)", vec![RoleSelector::new("button", false).into()]),
+            ("@role(`navigation`, 1)", vec![RoleSelector::new("navigation", true).into()]),
+            (
+                "@attrNames(`data-`)",
+                vec![AttrNamesSelector::new(Some("data-")).into()],
+            ),
 
             ("@id(`main`)", vec![IDSelector::new("main".into(), true).into()]),
             ("@id(`main`, 1)", vec![IDSelector::new("main".into(), true).into()]),
@@ -277,21 +849,71 @@ mod test {
             ("@class(`content-body`, 1)", vec![ClassSelector::new("content-body".into(), true).into()]),
             ("@class(`content-body`, 0)", vec![ClassSelector::new("content-body".into(), false).into()]),
 
-            ("#text()", vec![TextSelector::new().into()]),
+            ("@hasText()", vec![HasTextSelector::new(None).into()]),
+            ("@hasText(`Next page`)", vec![HasTextSelector::new(Some("Next page".into())).into()]),
+            ("@visible()", vec![VisibleTextSelector::new().into()]),
+            ("@title()", vec![TitleSelector::new().into()]),
+
+            ("#text()", vec![TextSelector::new(None, None).into()]),
+            ("#text(` `)", vec![TextSelector::new(Some(" ".into()), None).into()]),
+            ("#text(` `, `\n\n`)", vec![TextSelector::new(Some(" ".into()), Some("\n\n".into())).into()]),
+            ("#textWithBreaks()", vec![TextWithBreaksSelector::new().into()]),
+            ("#ownText()", vec![OwnTextSelector::new().into()]),
+            ("@pi()", vec![PiSelector::new(None).into()]),
+            ("@pi(`xml-stylesheet`)", vec![PiSelector::new(Some("xml-stylesheet".into())).into()]),
+            ("#textLen()", vec![TextLenSelector::new().into()]),
+            ("#leaves()", vec![LeavesSelector::new().into()]),
+            ("#textAll()", vec![TextAllSelector::new("".into()).into()]),
+            ("#textAll(`\\n`)", vec![TextAllSelector::new("\n".into()).into()]),
             ("#trim()", vec![TrimSelector::new().into()]),
+            ("#trimChars(`[]`)", vec![TrimCharsSelector::new("[]".into()).into()]),
             ("#trimPrefix(`hello`)", vec![TrimPrefixSelector::new("hello".into()).into()]),
             ("#trimSuffix(`world`)", vec![TrimSuffixSelector::new("world".into()).into()]),
+            ("#replaceLiteral(`&nbsp;`, ` `)", vec![ReplaceLiteralSelector::new("&nbsp;".into(), " ".into()).into()]),
+
+            ("#slice(0, 8)", vec![TextSliceSelector::new(0, 8).into()]),
+            ("#slice(-3, -1)", vec![TextSliceSelector::new(-3, -1).into()]),
+
+            ("#const(`-`)", vec![ConstSelector::new("-").into()]),
+            ("#const(``)", vec![ConstSelector::new("").into()]),
+            ("#index()", vec![IndexSelector::new().into()]),
+            ("#unique()", vec![UniqueTextSelector::new().into()]),
+            ("#tagName()", vec![TagNameSelector::new().into()]),
+            ("#capitalize()", vec![CapitalizeSelector::new().into()]),
+            ("#unixNewlines()", vec![UnixNewlinesSelector::new().into()]),
+            (
+                "#resolveUrl(`https://a.com/b/`)",
+                vec![ResolveUrlSelector::new(url::Url::parse("https://a.com/b/").unwrap()).into()],
+            ),
+            ("#titlecase()", vec![TitleCaseSelector::new().into()]),
+            ("#eq(`Next`)", vec![EqSelector::new("Next".into(), false, true).into()]),
+            ("#eq(`Next`, 1)", vec![EqSelector::new("Next".into(), true, true).into()]),
+            ("#eq(`Next`, 0, 0)", vec![EqSelector::new("Next".into(), false, false).into()]),
+            ("#repeat(3)", vec![RepeatSelector::new(3).into()]),
+            ("@sample(10)", vec![SampleSelector::new(10).into()]),
+            ("@takeUntil(@path(`//hr`))", vec![TakeUntilSelector::new(vec![PathSelector::new(vec![(Path::Travel, "hr".into())]).into()]).into()]),
+            ("@dropUntil(@class(`x`))", vec![DropUntilSelector::new(vec![ClassSelector::new("x".into(), true).into()]).into()]),
+            ("@depth(2)", vec![DepthSelector::new(2).into()]),
+
+            (r"#trimPrefix(`\t`)", vec![TrimPrefixSelector::new("\t".into()).into()]),
+            (r"#trimSuffix(`\r\n`)", vec![TrimSuffixSelector::new("\r\n".into()).into()]),
+            (r"#const(`a\\b`)", vec![ConstSelector::new("a\\b").into()]),
+            (r"#const(`\``)", vec![ConstSelector::new("`").into()]),
+            (r"#const(`\d`)", vec![ConstSelector::new("\\d").into()]),
+            (r"#replaceLiteral(`\n`, ` `)", vec![ReplaceLiteralSelector::new("\n".into(), " ".into()).into()]),
+            (r"@attr(`title`, `a\tb`)", vec![AttrSelector::new("title", Some("a\tb")).into()]),
 
             ("@child(0)", vec![NthChildSelector::new(0, false).into()]),
             ("@child(-0)", vec![NthChildSelector::new(0, false).into()]),
             ("@child(2)", vec![NthChildSelector::new(2, false).into()]),
             ("@child(-2)", vec![NthChildSelector::new(1, true).into()]),
+            ("@children()", vec![ChildrenSelector::new().into()]),
 
             ("@flat() | @path(`/body//div/a`) | @attr(`href`) | #text() | #trim()", vec![
                 FlatSelector::new().into(),
                 PathSelector::new(vec![(Path::Single, "body".into()), (Path::Travel, "div".into()), (Path::Single, "a".into())]).into(),
                 AttrSelector::new("href", None).into(),
-                TextSelector::new().into(),
+                TextSelector::new(None, None).into(),
                 TrimSelector::new().into(),
             ]),
         ];
@@ -301,4 +923,67 @@ mod test {
             assert_eq!(HqlParser::parse_stmt(pairs), selectors)
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_parse_json() {
+        let selectors = try_parse_hql("#json(`$.a[0].b-c`) | #json(`$`)").unwrap();
+        assert_eq!(
+            selectors,
+            vec![
+                JsonPathSelector::new(vec![
+                    JsonPathSegment::Key("a".into()),
+                    JsonPathSegment::Index(0),
+                    JsonPathSegment::Key("b-c".into()),
+                ])
+                .into(),
+                JsonPathSelector::new(vec![]).into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_overflow() {
+        let err = try_parse_hql("@child(99999999999999999999)").unwrap_err();
+        assert!(matches!(
+            err.variant,
+            pest::error::ErrorVariant::CustomError { .. }
+        ));
+        assert!(err
+            .to_string()
+            .contains("number 99999999999999999999 is out of range"));
+
+        for hql in [
+            "@child(-99999999999999999999)",
+            "@minChildren(99999999999999999999)",
+            "@sample(99999999999999999999)",
+            "#repeat(99999999999999999999)",
+            "#slice(0, 9223372036854775808)",
+            "#slice(-9223372036854775809, 0)",
+            "@path(`//p`) | @descendantOf(@depth(99999999999999999999))",
+        ] {
+            assert!(try_parse_hql(hql).is_err(), "{}", hql);
+        }
+
+        // the boundaries are fine
+        assert!(try_parse_hql("#slice(-9223372036854775808, 9223372036854775807)").is_ok());
+        assert!(try_parse_hql(&format!("@child({})", usize::MAX)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for hql in [
+            "@sample(0)",
+            "@sample(00)",
+            "@sample(-1)",
+            "#resolveUrl(`b/c`)",
+            "@matchAttr(`href`, `(`, 0)",
+            "@path(`//~`section-(``)",
+            "@path(`//child::a`)",
+            "@path(`parent::a`)",
+            "@attr(`type`, [])",
+        ] {
+            assert!(try_parse_hql(hql).is_err(), "{}", hql);
+        }
+    }
 }
@@ -1,8 +1,16 @@
-use crate::html::ElementOrTextRef;
+use std::{
+    collections::HashSet,
+    hash::{Hash, Hasher},
+};
 
-use super::Selector;
+use html5ever::{LocalName, Namespace, QualName};
+use regex::Regex;
 
-#[derive(Debug, Default, PartialEq)]
+use crate::html::{ElementOrTextRef, ElementRef, PhantomElementRef};
+
+use super::{yields_any, Selector, SelectorEnum};
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct FlatSelector;
 
 impl FlatSelector {
@@ -12,59 +20,878 @@ impl FlatSelector {
 }
 
 impl Selector for FlatSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_iter(node).collect()
+    }
+
+    fn select_iter<'a: 'i, 'b: 'i, 'i>(
+        &'b self,
+        node: ElementOrTextRef<'a>,
+    ) -> Box<dyn Iterator<Item = ElementOrTextRef<'a>> + 'i> {
+        Box::new(node.traverse_subtree())
+    }
+}
+
+/// TemplateContentSelector steps into the contents of `<template>` elements, which are opaque to
+/// other selectors. It yields children of the template contents and drops all other nodes.
+#[derive(Debug, Clone, Default, PartialEq, Hash)]
+pub struct TemplateContentSelector;
+
+impl TemplateContentSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for TemplateContentSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match node {
+            ElementOrTextRef::Element(e) => e.template_contents().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// ExcludeSelector drops Element nodes whose tag matches any of tags. Text and PhantomText nodes,
+/// including the text inside excluded elements, are passed through.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct ExcludeSelector {
+    tags: Vec<String>,
+}
+
+impl ExcludeSelector {
+    pub fn new(tags: Vec<String>) -> Self {
+        Self { tags }
+    }
+}
+
+impl Selector for ExcludeSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
-            .flat_map(|n| n.traverse_subtree())
+            .filter(|n| match n {
+                ElementOrTextRef::Element(e) => !self.tags.iter().any(|t| e.is_tag(t)),
+                _ => true,
+            })
             .collect()
     }
 }
 
-#[derive(Debug, PartialEq, Hash)]
+/// ChildrenSelector yields all element children of Element nodes, skipping text children. Other
+/// nodes are dropped.
+#[derive(Debug, Clone, Default, PartialEq, Hash)]
+pub struct ChildrenSelector;
+
+impl ChildrenSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for ChildrenSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match node {
+            ElementOrTextRef::Element(e) => e
+                .children(false)
+                .filter(|c| matches!(c, ElementOrTextRef::Element(_)))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// MinChildrenSelector keeps elements with at least n element children, dropping other nodes
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct MinChildrenSelector {
+    n: usize,
+}
+
+impl MinChildrenSelector {
+    pub fn new(n: usize) -> Self {
+        Self { n }
+    }
+}
+
+impl Selector for MinChildrenSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match &node {
+            ElementOrTextRef::Element(e) if e.child_element_count() >= self.n => vec![node],
+            _ => vec![],
+        }
+    }
+}
+
+/// MaxChildrenSelector keeps elements with at most n element children, dropping other nodes
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct MaxChildrenSelector {
+    n: usize,
+}
+
+impl MaxChildrenSelector {
+    pub fn new(n: usize) -> Self {
+        Self { n }
+    }
+}
+
+impl Selector for MaxChildrenSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match &node {
+            ElementOrTextRef::Element(e) if e.child_element_count() <= self.n => vec![node],
+            _ => vec![],
+        }
+    }
+}
+
+/// DescendantOfSelector keeps elements having an ancestor matched by the inner selectors, dropping
+/// other nodes. An ancestor matches when the inner selectors, evaluated from the ancestor, yield the
+/// ancestor itself, so the inner selectors are usually filters like `@attr()` or `@class()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescendantOfSelector {
+    selectors: Vec<SelectorEnum>,
+}
+
+impl DescendantOfSelector {
+    pub fn new(selectors: Vec<SelectorEnum>) -> Self {
+        Self { selectors }
+    }
+
+    fn matches(&self, ancestor: ElementRef) -> bool {
+        let ancestor = ElementOrTextRef::Element(ancestor);
+        let id = ancestor.document_position();
+        let mut nodes = vec![ancestor];
+        for s in &self.selectors {
+            nodes = s.select_batch(nodes);
+        }
+        nodes.iter().any(|n| n.document_position() == id)
+    }
+}
+
+impl Selector for DescendantOfSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match &node {
+            ElementOrTextRef::Element(e) if e.ancestors().any(|a| self.matches(a)) => vec![node],
+            _ => vec![],
+        }
+    }
+}
+
+/// AnySelector evaluates each inner pipeline from the node and unions their results, like
+/// "links or buttons", being the OR counterpart of a pipeline. Results keep the order of the
+/// pipelines, and a document node yielded by several pipelines is only kept the first time.
+/// Phantom nodes are never deduplicated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnySelector {
+    pipelines: Vec<Vec<SelectorEnum>>,
+}
+
+impl AnySelector {
+    pub fn new(pipelines: Vec<Vec<SelectorEnum>>) -> Self {
+        Self { pipelines }
+    }
+}
+
+impl Selector for AnySelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        // nodes navigated from one node share its document, so ids identify them
+        let mut seen = HashSet::new();
+        let mut results = vec![];
+        for selectors in &self.pipelines {
+            let mut nodes = vec![node.clone()];
+            for s in selectors {
+                nodes = s.select_batch(nodes);
+            }
+            results.extend(nodes.into_iter().filter(|n| match n {
+                ElementOrTextRef::Element(_) | ElementOrTextRef::Text(_) => {
+                    seen.insert(n.document_position())
+                }
+                _ => true,
+            }));
+        }
+        results
+    }
+}
+
+/// WrapSelector wraps each node into a new, attribute-less element with tag. The wrapper is a
+/// PhantomElement owning a copy of the node subtree, so it is detached from the document.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct WrapSelector {
+    tag: String,
+}
+
+impl WrapSelector {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_ascii_lowercase(),
+        }
+    }
+}
+
+impl Selector for WrapSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let name = QualName::new(None, ns!(html), LocalName::from(self.tag.as_str()));
+        vec![ElementOrTextRef::PhantomElement(PhantomElementRef::wrap(
+            name,
+            [&node],
+        ))]
+    }
+}
+
+/// DepthSelector keeps elements nested at depth n, which is the number of their ancestor elements.
+/// The root element `<html>` has depth 0, so top-level elements of a parsed fragment have depth 1.
+/// Other nodes are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthSelector {
+    n: usize,
+}
+
+impl DepthSelector {
+    pub fn new(n: usize) -> Self {
+        Self { n }
+    }
+}
+
+impl Selector for DepthSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match &node {
+            // stop counting early for deeply nested elements
+            ElementOrTextRef::Element(e) if e.ancestors().take(self.n + 1).count() == self.n => {
+                vec![node]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// SampleSelector is a batch-level selector keeping every n-th node of the whole node set, i.e.
+/// nodes at index 0, n, 2n, ... n is never 0, which is rejected when parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleSelector {
+    n: usize,
+}
+
+impl SampleSelector {
+    pub fn new(n: usize) -> Self {
+        debug_assert!(n > 0, "sample step must be positive");
+        Self { n }
+    }
+}
+
+impl Selector for SampleSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        nodes.into_iter().step_by(self.n.max(1)).collect()
+    }
+}
+
+/// TakeUntilSelector is a batch-level selector keeping nodes of the whole node set before the first
+/// one matched by the inner selectors, which is excluded. A node matches when the selectors
+/// evaluated from it yield anything. It works on the order of the node set, not per parent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TakeUntilSelector {
+    selectors: Vec<SelectorEnum>,
+}
+
+impl TakeUntilSelector {
+    pub fn new(selectors: Vec<SelectorEnum>) -> Self {
+        Self { selectors }
+    }
+}
+
+impl Selector for TakeUntilSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        nodes
+            .into_iter()
+            .take_while(|n| !yields_any(&self.selectors, n.clone()))
+            .collect()
+    }
+}
+
+/// DropUntilSelector is a batch-level selector dropping nodes of the whole node set before the
+/// first one matched by the inner selectors, which is kept. It is the complement of
+/// TakeUntilSelector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropUntilSelector {
+    selectors: Vec<SelectorEnum>,
+}
+
+impl DropUntilSelector {
+    pub fn new(selectors: Vec<SelectorEnum>) -> Self {
+        Self { selectors }
+    }
+}
+
+impl Selector for DropUntilSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        nodes
+            .into_iter()
+            .skip_while(|n| !yields_any(&self.selectors, n.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum Path {
+    /// Select matching children, like `/` or `child::`
     Single,
+    /// Select matching descendants, like `//` or `descendant::`
     Travel,
+    /// Keep the node itself if it matches, like `self::`
+    Current,
+}
+
+/// PathStep describes which elements one path step matches: an optional tag name, followed by
+/// optional css-like `.class` and `#id` qualifiers. A step without tag matches any tag.
+///
+/// Tags match by local name in any namespace, so `a` matches both HTML and SVG `<a>`, unless a
+/// namespace is given like `svg|a`.
+///
+/// A step can end with a `[last()]` or `[last()-k]` predicate like XPath, keeping only the last or
+/// k-before-last element among the siblings matching the rest of the step. Siblings are counted per
+/// parent for both `/` and `//` steps, so `//li[last()]` is the last `<li>` of each list, not the
+/// last `<li>` of the document.
+///
+/// Instead of a tag, a step can match tags by a regex like ``~`section-\d+` ``, which must match the
+/// whole local name. HTML tag names are lowercased by the parser, so the regex sees lowercase names.
+#[derive(Debug, Clone, Default)]
+pub struct PathStep {
+    ns: Option<Namespace>,
+    tag: Option<String>,
+    tag_regex: Option<Regex>,
+    classes: Vec<String>,
+    id: Option<String>,
+    from_last: Option<usize>,
+}
+
+impl PartialEq for PathStep {
+    fn eq(&self, other: &Self) -> bool {
+        self.ns == other.ns
+            && self.tag == other.tag
+            && self.tag_regex.as_ref().map(Regex::as_str)
+                == other.tag_regex.as_ref().map(Regex::as_str)
+            && self.classes == other.classes
+            && self.id == other.id
+            && self.from_last == other.from_last
+    }
+}
+
+impl Hash for PathStep {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ns.hash(state);
+        self.tag.hash(state);
+        self.tag_regex.as_ref().map(Regex::as_str).hash(state);
+        self.classes.hash(state);
+        self.id.hash(state);
+        self.from_last.hash(state);
+    }
 }
 
-#[derive(Debug, PartialEq, Hash)]
+impl PathStep {
+    pub fn new(tag: Option<String>) -> Self {
+        Self {
+            tag,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_tag(mut self, tag: String) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+
+    /// match tags by regex instead of name. The regex is anchored to match the whole tag name
+    pub fn with_tag_regex(mut self, regex: &str) -> Result<Self, regex::Error> {
+        self.tag_regex = Some(Regex::new(&format!("^(?:{regex})$"))?);
+        Ok(self)
+    }
+
+    pub fn with_class(mut self, class: String) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn with_ns(mut self, ns: Namespace) -> Self {
+        self.ns = Some(ns);
+        self
+    }
+
+    /// keep only the k-before-last matching sibling, `[last()-k]`
+    pub fn with_from_last(mut self, k: usize) -> Self {
+        self.from_last = Some(k);
+        self
+    }
+
+    fn matches(&self, e: &ElementRef) -> bool {
+        self.matches_element(e)
+            && self.from_last.is_none_or(|k| {
+                // stop counting once more than k following siblings match
+                e.next_siblings()
+                    .filter(|s| match s {
+                        ElementOrTextRef::Element(s) => self.matches_element(s),
+                        _ => false,
+                    })
+                    .take(k + 1)
+                    .count()
+                    == k
+            })
+    }
+
+    fn matches_element(&self, e: &ElementRef) -> bool {
+        self.ns
+            .as_ref()
+            .is_none_or(|ns| e.name().is_some_and(|n| &n.ns == ns))
+            && self.tag.as_ref().is_none_or(|t| e.is_tag(t))
+            && self
+                .tag_regex
+                .as_ref()
+                .is_none_or(|r| e.name().is_some_and(|n| r.is_match(&n.local)))
+            && self.classes.iter().all(|c| e.has_class(c, true))
+            && self.id.as_ref().is_none_or(|id| e.has_id(id, true))
+    }
+}
+
+impl From<&str> for PathStep {
+    fn from(tag: &str) -> Self {
+        Self::new(Some(tag.to_string()))
+    }
+}
+
+impl From<String> for PathStep {
+    fn from(tag: String) -> Self {
+        Self::new(Some(tag))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct PathSelector {
-    paths: Vec<(Path, String)>,
+    paths: Vec<(Path, PathStep)>,
 }
 
 impl PathSelector {
-    pub fn new(paths: Vec<(Path, String)>) -> Self {
+    pub fn new(paths: Vec<(Path, PathStep)>) -> Self {
         Self { paths }
     }
 }
 
 impl Selector for PathSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
-        let mut nodes = vec![node];
-        for (path, tag) in &self.paths {
-            nodes = match path {
-                Path::Single => nodes
-                    .into_iter()
-                    .flat_map(|n| n.traverse_children(false))
-                    .filter(|n| match n {
-                        ElementOrTextRef::Element(e) => {
-                            e.expanded_name().local.eq_str_ignore_ascii_case(tag)
-                        }
-                        _ => false,
-                    })
-                    .collect(),
-                Path::Travel => nodes
-                    .into_iter()
-                    .flat_map(|n| n.traverse_subtree())
-                    .filter(|n| match n {
-                        ElementOrTextRef::Element(e) => {
-                            e.expanded_name().local.eq_str_ignore_ascii_case(tag)
-                        }
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_iter(node).collect()
+    }
 
-                        _ => false,
-                    })
-                    .collect(),
+    fn select_iter<'a: 'i, 'b: 'i, 'i>(
+        &'b self,
+        node: ElementOrTextRef<'a>,
+    ) -> Box<dyn Iterator<Item = ElementOrTextRef<'a>> + 'i> {
+        let mut nodes: Box<dyn Iterator<Item = ElementOrTextRef<'a>> + 'i> =
+            Box::new(std::iter::once(node));
+        for (path, step) in &self.paths {
+            let matched = move |n: &ElementOrTextRef<'a>| match n {
+                ElementOrTextRef::Element(e) => step.matches(e),
+                _ => false,
+            };
+            nodes = match path {
+                Path::Single => Box::new(
+                    nodes
+                        .flat_map(|n| n.traverse_children(false))
+                        .filter(matched),
+                ),
+                Path::Travel => Box::new(nodes.flat_map(|n| n.traverse_subtree()).filter(matched)),
+                Path::Current => Box::new(nodes.filter(matched)),
             }
         }
 
         nodes
     }
 }
+
+#[cfg(test)]
+mod test {
+    use html5ever::QualName;
+
+    use crate::{
+        html::{ElementOrTextRef, Html},
+        querier::Querier,
+    };
+
+    fn query_ids(hql: &str, doc: &Html) -> Vec<String> {
+        let q = Querier::try_parse(hql).unwrap();
+        q.query_document(doc)
+            .iter()
+            .map(|n| match n {
+                ElementOrTextRef::Element(e) => e
+                    .get_attr(&QualName::new(None, ns!(), local_name!("id")))
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_any() {
+        let doc = Html::parse_fragment(
+            r#"<div id="d"><a id="a1"></a><button id="b1"></button><p><a id="a2" class="x"></a></p></div><a id="a3"></a>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids(
+                "@path(`//div`) | @any(@path(`//a`), @path(`//button`))",
+                &doc
+            ),
+            vec!["a1", "a2", "b1"]
+        );
+        // a node yielded by both pipelines is kept once
+        assert_eq!(
+            query_ids(
+                "@path(`//div`) | @any(@path(`//a`) | @class(`x`), @path(`//a`), @path(`/p`))",
+                &doc
+            ),
+            vec!["a2", "a1", ""]
+        );
+        assert!(query_ids("@path(`//div`) | @any(@path(`//i`), @path(`/b`))", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_path_tag_regex() {
+        let doc = Html::parse_fragment(
+            r#"<section-1 id="s1"><p id="p1"></p></section-1><section-22 id="s22"><p id="p2"></p></section-22>
+            <section-x id="sx"></section-x><my-section-3 id="m3"></my-section-3><section id="s"></section>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids(r"@path(`//~`section-\d+``)", &doc),
+            vec!["s1", "s22"]
+        );
+        assert_eq!(
+            query_ids(r"@path(`//~`section-\d+`/p`)", &doc),
+            vec!["p1", "p2"]
+        );
+        // qualifiers combine with the regex, which must match the whole tag name
+        assert_eq!(
+            query_ids(r"@path(`//~`(my-)?section-\d`#m3`)", &doc),
+            vec!["m3"]
+        );
+        assert_eq!(
+            query_ids(r"@path(`//~`section|p``)", &doc),
+            vec!["p1", "p2", "s"]
+        );
+    }
+
+    #[test]
+    fn test_path_axes() {
+        let doc = Html::parse_fragment(
+            r#"<div id="d1" class="x"><a id="a1"></a><p id="p1"><a id="a2"></a></p></div><div id="d2"></div>"#,
+            false,
+        );
+
+        // self:: filters the current nodes
+        assert_eq!(
+            query_ids("@path(`//div`) | @path(`self::div.x`)", &doc),
+            vec!["d1"]
+        );
+        assert!(query_ids("@path(`//div`) | @path(`self::p`)", &doc).is_empty());
+        assert_eq!(
+            query_ids("@path(`//div`) | @path(`self::div/child::a`)", &doc),
+            vec!["a1"]
+        );
+        // axes select like their shorthands
+        for (axis, short) in [
+            ("@path(`//div/child::a`)", "@path(`//div/a`)"),
+            ("@path(`//div/descendant::a`)", "@path(`//div//a`)"),
+            ("@path(`descendant::div`)", "@path(`//div`)"),
+        ] {
+            assert_eq!(query_ids(axis, &doc), query_ids(short, &doc), "{}", axis);
+        }
+    }
+
+    #[test]
+    fn test_wrap() {
+        let doc = Html::parse_document(
+            r#"<html><body><p id="a">one <b>two</b></p><p>three</p></body></html>"#,
+            false,
+        );
+
+        let q = Querier::try_parse("@path(`//p`) | @wrap(`section`)").unwrap();
+        let results = q.query_document(&doc);
+        assert_eq!(
+            results.iter().map(|n| n.as_text_cow()).collect::<Vec<_>>(),
+            vec![
+                r#"<section><p id="a">one <b>two</b></p></section>"#,
+                "<section><p>three</p></section>",
+            ]
+        );
+
+        // the document is left untouched
+        let q = Querier::try_parse("@path(`//section`)").unwrap();
+        assert!(q.query_document(&doc).is_empty());
+
+        // wrappers can be wrapped again, and text is wrapped as well
+        let q = Querier::try_parse("@path(`//b`) | #text() | @wrap(`em`) | @wrap(`div`)").unwrap();
+        assert_eq!(
+            Querier::results_to_string(&q.query_document(&doc), ""),
+            "<div><em>two</em></div>"
+        );
+    }
+
+    #[test]
+    fn test_min_max_children() {
+        let doc = Html::parse_document(
+            r#"<html><body>
+            <ul id="empty"></ul>
+            <ul id="one"><li>1</li></ul>
+            <ul id="three">text<li>1</li><li>2</li><!-- c --><li>3</li></ul>
+            <ul id="five"><li>1</li><li>2</li><li>3</li><li>4</li><li>5</li></ul>
+            </body></html>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids("@path(`//ul`) | @minChildren(3)", &doc),
+            vec!["three", "five"]
+        );
+        assert_eq!(
+            query_ids("@path(`//ul`) | @maxChildren(1)", &doc),
+            vec!["empty", "one"]
+        );
+        assert_eq!(
+            query_ids("@path(`//ul`) | @minChildren(1) | @maxChildren(3)", &doc),
+            vec!["one", "three"]
+        );
+        assert_eq!(query_ids("@path(`//ul`) | @minChildren(0)", &doc).len(), 4);
+        assert_eq!(
+            query_ids("@path(`//ul`) | @maxChildren(0)", &doc),
+            vec!["empty"]
+        );
+    }
+
+    #[test]
+    fn test_descendant_of() {
+        let doc = Html::parse_document(
+            r#"<html><body>
+            <nav class="menu"><ul><li><a id="home">home</a></li><li><a id="about">about</a></li></ul></nav>
+            <main><a id="inline">inline</a></main>
+            <footer class="menu"><a id="contact">contact</a></footer>
+            </body></html>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids("@path(`//a`) | @descendantOf(@class(`menu`))", &doc),
+            vec!["home", "about", "contact"]
+        );
+        // nested pipelines
+        assert_eq!(
+            query_ids(
+                "@path(`//a`) | @descendantOf(@class(`menu`) | @noAttr(`hidden`) | @hasText(`home`))",
+                &doc
+            ),
+            vec!["home", "about"]
+        );
+        // the element itself is not its ancestor
+        assert!(query_ids("@path(`//nav`) | @descendantOf(@class(`menu`))", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_template_content() {
+        let doc = Html::parse_document(
+            r#"<html><body><template id="t"><span id="in">in</span></template><span id="out">out</span></body></html>"#,
+            false,
+        );
+
+        // template contents are opaque by default
+        assert_eq!(query_ids("@path(`//span`)", &doc), vec!["out"]);
+        assert_eq!(
+            query_ids("@path(`//template/span`)", &doc),
+            Vec::<String>::new()
+        );
+
+        assert_eq!(
+            query_ids("@path(`//template`) | @templateContent()", &doc),
+            vec!["in"]
+        );
+        assert_eq!(
+            query_ids(
+                "@path(`//template`) | @templateContent() | @flat() | @path(`//span`)",
+                &doc
+            ),
+            vec!["in"]
+        );
+        assert_eq!(
+            query_ids("@path(`//span`) | @templateContent()", &doc),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_exclude() {
+        let doc = Html::parse_document(
+            r#"<html><head><style>p {}</style><script>let a;</script></head><body><p>content</p><noscript>no</noscript></body></html>"#,
+            false,
+        );
+
+        let q = Querier::try_parse("@flat() | @exclude(`script`, `style`)").unwrap();
+        let tags = q
+            .query_document(&doc)
+            .into_iter()
+            .filter_map(|n| match n {
+                ElementOrTextRef::Element(e) => Some(e.expanded_name().local.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tags, vec!["html", "head", "body", "p", "noscript"]);
+    }
+
+    #[test]
+    fn test_path_namespace() {
+        let doc = Html::parse_fragment(
+            r##"<a id="h" href="#">x</a><svg><a id="s"><text>y</text></a></svg>"##,
+            false,
+        );
+
+        assert_eq!(query_ids("@path(`//a`)", &doc), vec!["h", "s"]);
+        assert_eq!(query_ids("@path(`//svg|a`)", &doc), vec!["s"]);
+        assert_eq!(query_ids("@path(`//html|a`)", &doc), vec!["h"]);
+        assert!(query_ids("@path(`//math|a`)", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_path_last() {
+        let doc = Html::parse_fragment(
+            r#"<ul id="u1"><li id="a"></li><p id="p"></p><li id="b" class="x"></li><li id="c"></li></ul><ul id="u2"><li id="d" class="x"></li></ul>"#,
+            false,
+        );
+
+        assert_eq!(query_ids("@path(`//ul/li[last()]`)", &doc), vec!["c", "d"]);
+        assert_eq!(query_ids("@path(`//ul/li[last()-1]`)", &doc), vec!["b"]);
+        assert_eq!(query_ids("@path(`//ul/li[last()-2]`)", &doc), vec!["a"]);
+        assert!(query_ids("@path(`//ul/li[last()-3]`)", &doc).is_empty());
+        // counted among siblings matching the whole step
+        assert_eq!(query_ids("@path(`//li.x[last()]`)", &doc), vec!["b", "d"]);
+        assert_eq!(query_ids("@path(`//ul[last()]`)", &doc), vec!["u2"]);
+        assert_eq!(query_ids("@path(`//ul[last()]/li`)", &doc), vec!["d"]);
+    }
+
+    #[test]
+    fn test_path_step_qualifiers() {
+        let doc = Html::parse_fragment(
+            r#"<div class="content" id="main"></div><div class="content" id="side"></div><span id="main2" class="content"></span>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids("@path(`//div.content`)", &doc),
+            vec!["main", "side"]
+        );
+        assert_eq!(
+            query_ids("@path(`//.content`)", &doc),
+            vec!["main", "side", "main2"]
+        );
+        assert_eq!(query_ids("@path(`//#main`)", &doc), vec!["main"]);
+        assert_eq!(query_ids("@path(`//div.content#main`)", &doc), vec!["main"]);
+        assert_eq!(
+            query_ids("@path(`//span.content#main`)", &doc),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        let doc = Html::parse_fragment("<p>a</p>", false);
+
+        let q = Querier::try_parse("#const(`x`) | #repeat(25) | #index() | @sample(10)").unwrap();
+        assert_eq!(
+            q.query_document(&doc)
+                .iter()
+                .map(|n| n.as_text_cow().to_string())
+                .collect::<Vec<_>>(),
+            vec!["0", "10", "20"]
+        );
+    }
+
+    #[test]
+    fn test_depth() {
+        let doc = Html::parse_fragment(
+            r#"<div id="a"><p id="b"></p>text<section id="c"><p id="d"></p></section></div>"#,
+            false,
+        );
+
+        assert_eq!(query_ids("@flat() | @depth(1)", &doc), vec!["a"]);
+        assert_eq!(query_ids("@flat() | @depth(2)", &doc), vec!["b", "c"]);
+        assert_eq!(query_ids("@path(`//p`) | @depth(3)", &doc), vec!["d"]);
+        assert!(query_ids("@flat() | @depth(4)", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_take_drop_until() {
+        let doc = Html::parse_fragment(
+            r#"<li id="a"></li><li id="b"></li><li id="c" class="stop"></li><li id="d" class="stop"></li><li id="e"></li>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids("@path(`//li`) | @takeUntil(@class(`stop`))", &doc),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            query_ids("@path(`//li`) | @dropUntil(@class(`stop`))", &doc),
+            vec!["c", "d", "e"]
+        );
+        // nested selectors are evaluated from each node
+        assert_eq!(
+            query_ids("@path(`//li`) | @dropUntil(#attr(`id`) | #eq(`d`))", &doc),
+            vec!["d", "e"]
+        );
+        // no node matches
+        assert_eq!(
+            query_ids("@path(`//li`) | @takeUntil(@class(`x`))", &doc).len(),
+            5
+        );
+        assert!(query_ids("@path(`//li`) | @dropUntil(@class(`x`))", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_children() {
+        let doc = Html::parse_fragment(
+            r#"<ul id="u">text<li id="a"><span id="s"></span></li> <li id="b"></li><!-- c --><li id="c"></li></ul>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_ids("@path(`//ul`) | @children()", &doc),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            query_ids("@path(`//ul`) | @children() | @children()", &doc),
+            vec!["s"]
+        );
+        assert!(query_ids("@path(`//ul`) | #text() | @children()", &doc).is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use crate::html::ElementOrTextRef;
+
+use super::{yields_any, Selector, SelectorEnum};
+
+/// AdjacentSelector selects the element immediately following an element, like CSS `a + b`. Text
+/// between them is skipped. It yields nothing if the following element does not match tag, or
+/// there is no following element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdjacentSelector {
+    tag: String,
+}
+
+impl AdjacentSelector {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+        }
+    }
+}
+
+impl Selector for AdjacentSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let ElementOrTextRef::Element(e) = node else {
+            return vec![];
+        };
+
+        e.next_siblings()
+            .find(|n| matches!(n, ElementOrTextRef::Element(_)))
+            .filter(|n| matches!(n, ElementOrTextRef::Element(s) if s.is_tag(&self.tag)))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// PrevTextSelector yields the text of the element immediately preceding an element as PhantomText,
+/// like the `<dt>` label of a `<dd>` value. Text between them is skipped. It yields nothing if
+/// there is no preceding element.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrevTextSelector;
+
+impl PrevTextSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for PrevTextSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let ElementOrTextRef::Element(e) = node else {
+            return vec![];
+        };
+
+        e.prev_siblings()
+            .find_map(|n| match n {
+                ElementOrTextRef::Element(p) => {
+                    Some(ElementOrTextRef::new_phantom_from_txt(p.text_content()))
+                }
+                _ => None,
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// FollowingSelector selects all following element siblings of an element with the tag, like css
+/// `a ~ b`. It yields nothing for the last child.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowingSelector {
+    tag: String,
+}
+
+impl FollowingSelector {
+    pub fn new(tag: &str) -> Self {
+        Self {
+            tag: tag.to_string(),
+        }
+    }
+}
+
+impl Selector for FollowingSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let ElementOrTextRef::Element(e) = node else {
+            return vec![];
+        };
+
+        e.next_siblings()
+            .filter(|n| matches!(n, ElementOrTextRef::Element(s) if s.is_tag(&self.tag)))
+            .collect()
+    }
+}
+
+/// BetweenSelector selects the element siblings following each element matched by start, up to
+/// but excluding the first one matched by end, or up to the last sibling if none matches. Both
+/// boundaries are excluded. A node matches when the selectors evaluated from it yield anything, so
+/// filters like `@class()`, or `#tagName()` followed by `#eq()`, make predicates. Other nodes are
+/// dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BetweenSelector {
+    start: Vec<SelectorEnum>,
+    end: Vec<SelectorEnum>,
+}
+
+impl BetweenSelector {
+    pub fn new(start: Vec<SelectorEnum>, end: Vec<SelectorEnum>) -> Self {
+        Self { start, end }
+    }
+}
+
+impl Selector for BetweenSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let ElementOrTextRef::Element(e) = &node else {
+            return vec![];
+        };
+        if !yields_any(&self.start, node.clone()) {
+            return vec![];
+        }
+
+        e.next_siblings()
+            .filter(|n| matches!(n, ElementOrTextRef::Element(_)))
+            .take_while(|n| !yields_any(&self.end, n.clone()))
+            .collect()
+    }
+}
+
+/// LastPerParentSelector is a batch-level selector grouping the whole node set by parent element,
+/// keeping only the last node of each group in document order. The kept nodes stay in the order of
+/// the node set. Nodes without a parent element, like phantom nodes, are each a group of their own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LastPerParentSelector;
+
+impl LastPerParentSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for LastPerParentSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        let parents: Vec<_> = nodes
+            .iter()
+            .map(|n| {
+                n.parent()
+                    .map(|p| ElementOrTextRef::Element(p).document_position())
+            })
+            .collect();
+
+        // index of the last node of each parent
+        let mut last = HashMap::new();
+        for (i, (node, parent)) in nodes.iter().zip(&parents).enumerate() {
+            let Some(parent) = parent else { continue };
+            last.entry(*parent)
+                .and_modify(|j: &mut usize| {
+                    if nodes[*j].document_position() < node.document_position() {
+                        *j = i;
+                    }
+                })
+                .or_insert(i);
+        }
+
+        nodes
+            .into_iter()
+            .zip(parents)
+            .enumerate()
+            .filter(|(i, (_, parent))| parent.is_none_or(|p| last[&p] == *i))
+            .map(|(_, (node, _))| node)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        html::{Html, QueryResult},
+        querier::Querier,
+    };
+
+    fn query_texts(hql: &str, doc: &Html) -> Vec<String> {
+        let q = Querier::try_parse(hql).unwrap();
+        q.query_document(doc)
+            .iter()
+            .map(|n| QueryResult::from(n).text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_adjacent() {
+        let doc = Html::parse_fragment(
+            "<dl><dt>a</dt> <dd>1</dd><dt>b</dt>text<p>x</p><dt>c</dt></dl>",
+            false,
+        );
+
+        // matching adjacent element, skipping whitespace text
+        assert_eq!(
+            query_texts("@path(`//dt`) | @adjacent(`dd`)", &doc),
+            vec!["1"]
+        );
+        // `dd` after the first `dt` is not a `p`, and the last `dt` has no following element
+        assert_eq!(
+            query_texts("@path(`//dt`) | @adjacent(`p`)", &doc),
+            vec!["x"]
+        );
+        // no following sibling at all
+        assert!(query_texts("@path(`//dd`) | @adjacent(`dt`) | @adjacent(`dt`)", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_prev_text() {
+        let doc = Html::parse_fragment(
+            "<dl><dt>Key</dt><dd>Value</dd><dt>Other <b>key</b></dt> text <dd>2</dd></dl>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//dd`) | @prevText()", &doc),
+            vec!["Key", "Other key"]
+        );
+        // no preceding element
+        assert!(query_texts("@path(`//dl`) | @child(0) | @prevText()", &doc).is_empty());
+        assert!(query_texts("@path(`//dd`) | #text() | @prevText()", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_following() {
+        let doc = Html::parse_fragment(
+            "<ul><li>1</li> <li>2</li><p>x</p>text<li>3</li></ul>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//ul`) | @child(0) | @following(`li`)", &doc),
+            vec!["2", "3"]
+        );
+        // the last child has no following sibling
+        assert!(query_texts("@path(`//ul`) | @child(-1) | @following(`li`)", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_between() {
+        let doc = Html::parse_fragment(
+            "<h2>A</h2><p>1</p>text<p>2</p><h2>B</h2><p>3</p><h3>C</h3><p>4</p>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts(
+                "@path(`//h2`) | @between(#text() | #eq(`A`), #tagName() | #eq(`h2`))",
+                &doc
+            ),
+            vec!["1", "2"]
+        );
+        // up to the last sibling without end marker
+        assert_eq!(
+            query_texts(
+                "@path(`//h3`) | @between(@flat(), #tagName() | #eq(`h2`))",
+                &doc
+            ),
+            vec!["4"]
+        );
+    }
+
+    #[test]
+    fn test_last_per_parent() {
+        let doc = Html::parse_fragment(
+            "<ul><li>1</li><li>2</li></ul><ol><li>a</li><li>b</li><li>c</li></ol>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//li`) | @lastPerParent()", &doc),
+            vec!["2", "c"]
+        );
+        // lists share the same parent
+        assert_eq!(
+            query_texts("@path(`//html`) | @children() | @lastPerParent()", &doc),
+            vec!["abc"]
+        );
+        // phantom text has no parent, so each one is kept
+        assert_eq!(
+            query_texts("@path(`//li`) | #text() | @lastPerParent()", &doc),
+            vec!["1", "2", "a", "b", "c"]
+        );
+    }
+}
@@ -1,61 +1,624 @@
-use std::str::FromStr;
+use std::collections::HashSet;
 
-use html5ever::tendril::StrTendril;
+use html5ever::{tendril::StrTendril, QualName};
+use url::Url;
 
-use crate::html::ElementOrTextRef;
+use crate::html::{ElementOrTextRef, ElementRef};
 
-use super::Selector;
+use super::{attr::inline_style, Selector};
 
-#[derive(Debug, Default, PartialEq)]
-pub struct TextSelector;
+/// TextSelector replaces each element with the text of its subtree. The document root yields the
+/// text of the whole page, and elements detached from the document, like those from `@wrap()`, the
+/// text of their own subtree. Text nodes already are text, so they are kept as is, which makes
+/// `#text()` safe to apply on a node set mixing elements and text.
+///
+/// With separators, element text is laid out block by block like `ElementRef::text_blocks`:
+/// whitespace inside a block becomes sep, and blocks are joined by block_sep, which defaults to sep.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextSelector {
+    sep: Option<String>,
+    block_sep: Option<String>,
+}
 
 impl TextSelector {
-    pub fn new() -> Self {
-        TextSelector
+    pub fn new(sep: Option<String>, block_sep: Option<String>) -> Self {
+        Self { sep, block_sep }
+    }
+
+    fn text_of(&self, e: &ElementRef) -> StrTendril {
+        match &self.sep {
+            Some(sep) => {
+                StrTendril::from(e.text_blocks(sep, self.block_sep.as_deref().unwrap_or(sep)))
+            }
+            None => e.text_content(),
+        }
     }
 }
 
 impl Selector for TextSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .map(|n| match n {
                 ElementOrTextRef::Element(e) => {
-                    ElementOrTextRef::new_phantom_from_txt(e.text().map(|t| t.text()).collect())
+                    ElementOrTextRef::new_phantom_from_txt(self.text_of(&e))
+                }
+                ElementOrTextRef::PhantomElement(e) => {
+                    ElementOrTextRef::new_phantom_from_txt(self.text_of(&e.element()))
                 }
-                _ => n,
+                ElementOrTextRef::Text(_) | ElementOrTextRef::PhantomText(_) => n,
             })
             .collect()
     }
 }
 
-/// TrimSelector will only handle Text and PhantomText nodes and ignore element nodes
-#[derive(Debug, Default, PartialEq)]
-pub struct TrimSelector;
+/// TextWithBreaksSelector works like TextSelector, but inserts `\n` for each `<br>` and `<hr>`
+/// element in the subtree
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextWithBreaksSelector;
 
-impl TrimSelector {
+impl TextWithBreaksSelector {
     pub fn new() -> Self {
         Self
     }
 }
 
-impl Selector for TrimSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+impl Selector for TextWithBreaksSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .map(|n| match n {
-                ElementOrTextRef::Element(_) => n,
-                ElementOrTextRef::Text(t) => ElementOrTextRef::new_phantom_from_txt(
-                    StrTendril::from_str(t.text().text().clone().trim()).unwrap(),
-                ),
-                ElementOrTextRef::PhantomText(t) => ElementOrTextRef::new_phantom_from_txt(
-                    StrTendril::from_str(t.text().text().clone().trim()).unwrap(),
+                ElementOrTextRef::Element(e) => {
+                    ElementOrTextRef::new_phantom_from_txt(StrTendril::from(e.text_with_breaks()))
+                }
+                ElementOrTextRef::PhantomElement(e) => ElementOrTextRef::new_phantom_from_txt(
+                    StrTendril::from(e.element().text_with_breaks()),
                 ),
+                ElementOrTextRef::Text(_) | ElementOrTextRef::PhantomText(_) => n,
+            })
+            .collect()
+    }
+}
+
+/// OwnTextSelector concatenates only the direct Text children of an element, excluding text
+/// of nested elements. Text and PhantomText nodes are passed through like TextSelector
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OwnTextSelector;
+
+impl OwnTextSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for OwnTextSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let own_text = |e: ElementRef| -> StrTendril {
+            e.children(false)
+                .filter_map(|c| match c {
+                    ElementOrTextRef::Text(t) => Some(t.text().text()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        std::iter::once(node)
+            .map(|n| match n {
+                ElementOrTextRef::Element(e) => ElementOrTextRef::new_phantom_from_txt(own_text(e)),
+                ElementOrTextRef::PhantomElement(e) => {
+                    ElementOrTextRef::new_phantom_from_txt(own_text(e.element()))
+                }
+                ElementOrTextRef::Text(_) | ElementOrTextRef::PhantomText(_) => n,
+            })
+            .collect()
+    }
+}
+
+/// LeavesSelector yields each text node in the subtree of elements separately, in document order,
+/// instead of concatenating them like TextSelector. Every text node is a leaf, since text nodes
+/// never have children, so text directly inside an element with child elements is yielded too.
+/// Text and PhantomText nodes yield themselves. Text of detached PhantomElement nodes is yielded as
+/// PhantomText.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LeavesSelector;
+
+impl LeavesSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for LeavesSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match node {
+            ElementOrTextRef::Element(_) | ElementOrTextRef::Text(_) => node
+                .traverse_subtree()
+                .filter(|n| matches!(n, ElementOrTextRef::Text(_)))
+                .collect(),
+            ElementOrTextRef::PhantomElement(e) => e
+                .element()
+                .text()
+                .map(|t| ElementOrTextRef::new_phantom_from_txt(t.text().clone()))
+                .collect(),
+            ElementOrTextRef::PhantomText(_) => vec![node],
+        }
+    }
+}
+
+/// TextAllSelector is a batch-level selector concatenating the text of all nodes of the whole node
+/// set into a single PhantomText, joined by the separator. Elements contribute their aggregated
+/// subtree text like TextSelector, so there is no need to apply `#text()` before. An empty node set
+/// yields nothing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextAllSelector {
+    sep: String,
+}
+
+impl TextAllSelector {
+    pub fn new(sep: String) -> Self {
+        Self { sep }
+    }
+}
+
+impl Selector for TextAllSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        if nodes.is_empty() {
+            return vec![];
+        }
+
+        let mut text = StrTendril::new();
+        for (i, n) in nodes.iter().enumerate() {
+            if i > 0 {
+                text.push_slice(&self.sep);
+            }
+            match n {
+                ElementOrTextRef::Element(e) => text.push_tendril(&e.text_content()),
+                ElementOrTextRef::PhantomElement(e) => {
+                    text.push_tendril(&e.element().text_content())
+                }
+                ElementOrTextRef::Text(t) => text.push_tendril(t.text().text()),
+                ElementOrTextRef::PhantomText(t) => text.push_tendril(t.text().text()),
+            }
+        }
+        vec![ElementOrTextRef::new_phantom_from_txt(text)]
+    }
+}
+
+/// TextLenSelector replaces each node with a PhantomText of the number of characters of its text,
+/// which is the aggregated subtree text for elements like TextSelector. Lengths are summed over the
+/// text nodes without concatenating them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLenSelector;
+
+impl TextLenSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for TextLenSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let subtree_len = |e: &ElementRef| e.text().map(|t| t.text().chars().count()).sum();
+        let len: usize = match &node {
+            ElementOrTextRef::Element(e) => subtree_len(e),
+            ElementOrTextRef::PhantomElement(e) => subtree_len(&e.element()),
+            ElementOrTextRef::Text(t) => t.text().text().chars().count(),
+            ElementOrTextRef::PhantomText(t) => t.text().text().chars().count(),
+        };
+        vec![ElementOrTextRef::new_phantom_from_txt(StrTendril::from(
+            len.to_string(),
+        ))]
+    }
+}
+
+/// HasTextSelector keeps nodes by their text without converting them into text, so elements are kept
+/// as elements. Elements are tested with their aggregated subtree text, and Text and PhantomText
+/// nodes with their own text.
+///
+/// Without substr, it keeps nodes whose text is non-empty. Otherwise, it keeps nodes whose text
+/// contains substr.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HasTextSelector {
+    substr: Option<String>,
+}
+
+impl HasTextSelector {
+    pub fn new(substr: Option<String>) -> Self {
+        Self { substr }
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match &self.substr {
+            None => !text.is_empty(),
+            Some(s) => text.contains(s.as_str()),
+        }
+    }
+}
+
+impl Selector for HasTextSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let element_matches = |e: &ElementRef| match &self.substr {
+            // avoid building the whole text when only checking emptiness
+            None => e.text().any(|t| !t.text().is_empty()),
+            Some(_) => self.matches(&e.text_content()),
+        };
+
+        std::iter::once(node)
+            .filter(|n| match n {
+                ElementOrTextRef::Element(e) => element_matches(e),
+                ElementOrTextRef::PhantomElement(e) => element_matches(&e.element()),
+                ElementOrTextRef::Text(t) => self.matches(t.text().text()),
+                ElementOrTextRef::PhantomText(t) => self.matches(t.text().text()),
+            })
+            .collect()
+    }
+}
+
+/// VisibleTextSelector keeps nodes whose text has any non-whitespace char, like HasTextSelector
+/// keeping elements as elements. It drops spacer elements containing only whitespace, and nodes
+/// hidden by themselves or an ancestor element. Only explicit signals on elements count as hidden:
+/// an inline `display: none` style, the `hidden` attribute and `aria-hidden="true"`. Stylesheets
+/// are not applied.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisibleTextSelector;
+
+impl VisibleTextSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for VisibleTextSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let visible = |s: &str| s.chars().any(|c| !c.is_whitespace());
+        // stop at the first visible text node without building the whole text
+        let element_matches = |e: &ElementRef| e.text().any(|t| visible(t.text()));
+        let shown = |e: &ElementRef| !is_hidden(e) && !e.ancestors().any(|a| is_hidden(&a));
+
+        std::iter::once(node)
+            .filter(|n| match n {
+                ElementOrTextRef::Element(e) => shown(e) && element_matches(e),
+                ElementOrTextRef::PhantomElement(e) => element_matches(&e.element()),
+                ElementOrTextRef::Text(_) if n.parent().is_some_and(|p| !shown(&p)) => false,
+                ElementOrTextRef::Text(t) => visible(t.text().text()),
+                ElementOrTextRef::PhantomText(t) => visible(t.text().text()),
+            })
+            .collect()
+    }
+}
+
+/// Whether the element itself is explicitly hidden, see VisibleTextSelector
+fn is_hidden(e: &ElementRef) -> bool {
+    let attr = |name| e.get_attr(&QualName::new(None, ns!(), name));
+    attr(local_name!("hidden")).is_some()
+        || attr(local_name!("aria-hidden")).is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+        || inline_style(e, "display").is_some_and(|v| v.eq_ignore_ascii_case("none"))
+}
+
+/// PiSelector yields the data of processing instructions like `<?xml-stylesheet href="a.xsl"?>` in
+/// the subtree of elements as PhantomText, keeping those with the target if given. Text nodes have
+/// no processing instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiSelector {
+    target: Option<String>,
+}
+
+impl PiSelector {
+    pub fn new(target: Option<String>) -> Self {
+        Self { target }
+    }
+
+    fn data<'a>(&self, e: &ElementRef) -> Vec<ElementOrTextRef<'a>> {
+        e.processing_instructions()
+            .filter(|pi| self.target.as_ref().is_none_or(|t| **pi.target() == **t))
+            .map(|pi| ElementOrTextRef::new_phantom_from_txt(pi.data().clone()))
+            .collect()
+    }
+}
+
+impl Selector for PiSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        match node {
+            ElementOrTextRef::Element(e) => self.data(&e),
+            ElementOrTextRef::PhantomElement(e) => self.data(&e.element()),
+            ElementOrTextRef::Text(_) | ElementOrTextRef::PhantomText(_) => vec![],
+        }
+    }
+}
+
+/// TitleSelector is a batch-level selector yielding the document title as PhantomText, whatever the
+/// node set is. The title is the text of the first HTML `<title>` element in the document of the
+/// first non-phantom node, with whitespace collapsed like browsers do. `<title>` of SVG is not the
+/// document title. Nothing is yielded without a title element.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TitleSelector;
+
+impl TitleSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for TitleSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        let Some(root) = nodes.iter().find_map(|n| n.document_root()) else {
+            return vec![];
+        };
+
+        ElementOrTextRef::Element(root)
+            .traverse_subtree()
+            .find_map(|n| match n {
+                ElementOrTextRef::Element(e)
+                    if e.name()
+                        .is_some_and(|n| n.ns == ns!(html) && &n.local == "title") =>
+                {
+                    Some(e)
+                }
+                _ => None,
+            })
+            .map(|e| {
+                let title = e
+                    .text_content()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                ElementOrTextRef::new_phantom_from_txt(StrTendril::from(title))
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// EqSelector keeps nodes whose text equals value, like HasTextSelector keeping elements as
+/// elements. When trim is set, leading and trailing whitespace of text is trimmed before comparison,
+/// and the value itself is compared as given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EqSelector {
+    value: String,
+    trim: bool,
+    case_sensitive: bool,
+}
+
+impl EqSelector {
+    pub fn new(value: String, trim: bool, case_sensitive: bool) -> Self {
+        Self {
+            value,
+            trim,
+            case_sensitive,
+        }
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        let text = match self.trim {
+            true => text.trim(),
+            false => text,
+        };
+        match self.case_sensitive {
+            true => text == self.value,
+            false => text.to_lowercase() == self.value.to_lowercase(),
+        }
+    }
+}
+
+impl Selector for EqSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        std::iter::once(node)
+            .filter(|n| match n {
+                ElementOrTextRef::Element(e) => self.matches(&e.text_content()),
+                ElementOrTextRef::PhantomElement(e) => self.matches(&e.element().text_content()),
+                ElementOrTextRef::Text(t) => self.matches(t.text().text()),
+                ElementOrTextRef::PhantomText(t) => self.matches(t.text().text()),
             })
             .collect()
     }
 }
 
+/// Replace the text of Text and PhantomText nodes with f applied to it as PhantomText, passing
+/// through Element and PhantomElement nodes. Text transforms like `#trim()` are built on it.
+fn map_text<'a>(
+    node: ElementOrTextRef<'a>,
+    f: impl Fn(&str) -> StrTendril,
+) -> ElementOrTextRef<'a> {
+    match &node {
+        ElementOrTextRef::Element(_) | ElementOrTextRef::PhantomElement(_) => node,
+        ElementOrTextRef::Text(t) => ElementOrTextRef::new_phantom_from_txt(f(t.text().text())),
+        ElementOrTextRef::PhantomText(t) => {
+            ElementOrTextRef::new_phantom_from_txt(f(t.text().text()))
+        }
+    }
+}
+
+/// TrimSelector will only handle Text and PhantomText nodes and ignore element nodes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrimSelector;
+
+impl TrimSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for TrimSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| StrTendril::from_slice(s.trim()))]
+    }
+}
+
+/// TrimCharsSelector trims leading and trailing chars found in chars from Text and PhantomText
+/// nodes, like `#trim()` with a custom char set. Element nodes are passed through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrimCharsSelector {
+    chars: String,
+}
+
+impl TrimCharsSelector {
+    pub fn new(chars: String) -> Self {
+        Self { chars }
+    }
+
+    fn trim<'s>(&self, s: &'s str) -> &'s str {
+        s.trim_matches(|c| self.chars.contains(c))
+    }
+}
+
+impl Selector for TrimCharsSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| StrTendril::from_slice(self.trim(s)))]
+    }
+}
+
+/// AsciiFoldSelector transliterates Text and PhantomText nodes into ASCII, like `café` to `cafe`
+/// and `北京` to `Bei Jing`, which is helpful for slugs and keys. Chars without ASCII equivalent,
+/// like emoji without a name, are replaced with `?`. Element nodes are passed through.
+#[cfg(feature = "ascii")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AsciiFoldSelector;
+
+#[cfg(feature = "ascii")]
+impl AsciiFoldSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(feature = "ascii")]
+impl Selector for AsciiFoldSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| {
+            StrTendril::from(deunicode::deunicode_with_tofu(s, "?"))
+        })]
+    }
+}
+
+/// Uppercase the first non-whitespace char of each word, or only of the first word. Other chars
+/// are kept as is.
+fn uppercase_words(s: &str, all_words: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut word_start = true;
+    let mut done = false;
+    for c in s.chars() {
+        if c.is_whitespace() {
+            word_start = true;
+            out.push(c);
+        } else if word_start && !done {
+            out.extend(c.to_uppercase());
+            word_start = false;
+            done = !all_words;
+        } else {
+            word_start = false;
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// CapitalizeSelector uppercases the first letter of Text and PhantomText nodes, and passes through
+/// Element nodes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapitalizeSelector;
+
+impl CapitalizeSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for CapitalizeSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| {
+            StrTendril::from(uppercase_words(s, false))
+        })]
+    }
+}
+
+/// TitleCaseSelector uppercases the first letter of each whitespace separated word of Text and
+/// PhantomText nodes, and passes through Element nodes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TitleCaseSelector;
+
+impl TitleCaseSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for TitleCaseSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| {
+            StrTendril::from(uppercase_words(s, true))
+        })]
+    }
+}
+
+/// UnixNewlinesSelector converts `\r\n` and lone `\r` of Text and PhantomText nodes into `\n`,
+/// and passes through Element nodes. Note html5ever already normalizes newlines of parsed
+/// documents, so this is mostly needed for text from attributes escaped like `&#13;` or from JSON.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnixNewlinesSelector;
+
+impl UnixNewlinesSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for UnixNewlinesSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| {
+            StrTendril::from(s.replace("\r\n", "\n").replace('\r', "\n"))
+        })]
+    }
+}
+
+/// ResolveUrlSelector resolves the text of Text and PhantomText nodes as a URL reference against
+/// the base URL, like a browser resolving `href`. Absolute and protocol-relative URLs are resolved
+/// as well, so they are kept, only normalized like `HTTP://A.com` to `http://a.com/`. Text which
+/// can not be resolved, like `http://[bad`, is dropped. Element nodes are passed through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveUrlSelector {
+    base: Url,
+}
+
+impl ResolveUrlSelector {
+    pub fn new(base: Url) -> Self {
+        Self { base }
+    }
+}
+
+impl Selector for ResolveUrlSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let text = match &node {
+            ElementOrTextRef::Element(_) | ElementOrTextRef::PhantomElement(_) => {
+                return vec![node]
+            }
+            ElementOrTextRef::Text(t) => t.text().text(),
+            ElementOrTextRef::PhantomText(t) => t.text().text(),
+        };
+
+        self.base
+            .join(text)
+            .map(|u| ElementOrTextRef::new_phantom_from_txt(StrTendril::from(String::from(u))))
+            .into_iter()
+            .collect()
+    }
+}
+
 /// TrimPrefixSelector will only handle Text and PhantomText nodes and ignore element nodes
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TrimPrefixSelector {
     prefix: String,
 }
@@ -67,27 +630,15 @@ impl TrimPrefixSelector {
 }
 
 impl Selector for TrimPrefixSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
-        std::iter::once(node)
-            .map(|n| match n {
-                ElementOrTextRef::Element(_) => n,
-                ElementOrTextRef::Text(t) => {
-                    let t = t.text().text().clone();
-                    let striped = t.strip_prefix(&self.prefix).unwrap_or(&t);
-                    ElementOrTextRef::new_phantom_from_txt(StrTendril::from_str(striped).unwrap())
-                }
-                ElementOrTextRef::PhantomText(t) => {
-                    let t = t.text().text().clone();
-                    let striped = t.strip_prefix(&self.prefix).unwrap_or(&t);
-                    ElementOrTextRef::new_phantom_from_txt(StrTendril::from_str(striped).unwrap())
-                }
-            })
-            .collect()
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| {
+            StrTendril::from_slice(s.strip_prefix(&self.prefix).unwrap_or(s))
+        })]
     }
 }
 
 /// TrimSuffixSelector will only handle Text and PhantomText nodes and ignore element nodes
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TrimSuffixSelector {
     suffix: String,
 }
@@ -99,27 +650,216 @@ impl TrimSuffixSelector {
 }
 
 impl Selector for TrimSuffixSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
-        std::iter::once(node)
-            .map(|n| match n {
-                ElementOrTextRef::Element(_) => n,
-                ElementOrTextRef::Text(t) => {
-                    let t = t.text().text().clone();
-                    let striped = t.strip_suffix(&self.suffix).unwrap_or(&t);
-                    ElementOrTextRef::new_phantom_from_txt(StrTendril::from_str(striped).unwrap())
-                }
-                ElementOrTextRef::PhantomText(t) => {
-                    let t = t.text().text().clone();
-                    let striped = t.strip_suffix(&self.suffix).unwrap_or(&t);
-                    ElementOrTextRef::new_phantom_from_txt(StrTendril::from_str(striped).unwrap())
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| {
+            StrTendril::from_slice(s.strip_suffix(&self.suffix).unwrap_or(s))
+        })]
+    }
+}
+
+/// ReplaceLiteralSelector replaces all occurrences of the literal `from` with `to` in Text and
+/// PhantomText nodes and ignores element nodes. An empty `from` leaves text unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaceLiteralSelector {
+    from: String,
+    to: String,
+}
+
+impl ReplaceLiteralSelector {
+    pub fn new(from: String, to: String) -> Self {
+        Self { from, to }
+    }
+
+    fn replace(&self, s: &str) -> String {
+        match self.from.is_empty() {
+            true => s.to_string(),
+            false => s.replace(&self.from, &self.to),
+        }
+    }
+}
+
+impl Selector for ReplaceLiteralSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| StrTendril::from(self.replace(s)))]
+    }
+}
+
+/// TextSliceSelector takes the characters in `[start, end)` of Text and PhantomText nodes and ignores
+/// element nodes.
+///
+/// Indices count unicode scalar values rather than bytes. Negative indices count from the end of
+/// text, and out-of-range indices are clamped, yielding empty text when start is not before end.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSliceSelector {
+    start: isize,
+    end: isize,
+}
+
+impl TextSliceSelector {
+    pub fn new(start: isize, end: isize) -> Self {
+        Self { start, end }
+    }
+
+    fn slice<'s>(&self, s: &'s str) -> &'s str {
+        let len = s.chars().count() as isize;
+        let clamp = |i: isize| match i < 0 {
+            true => (len + i).max(0),
+            false => i.min(len),
+        } as usize;
+
+        let (start, end) = (clamp(self.start), clamp(self.end));
+        if start >= end {
+            return "";
+        }
+
+        // map char indices to byte offsets, with len as the end of string
+        let mut offsets = s
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(s.len()));
+        // start < end <= char count after clamping, so both offsets exist
+        let start_byte = offsets.nth(start).expect("start is out of bounds");
+        let end_byte = offsets.nth(end - start - 1).expect("end is out of bounds");
+        &s[start_byte..end_byte]
+    }
+}
+
+impl Selector for TextSliceSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![map_text(node, |s| StrTendril::from_slice(self.slice(s)))]
+    }
+}
+
+/// ConstSelector replaces any node with a PhantomText of the fixed value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstSelector {
+    value: StrTendril,
+}
+
+impl ConstSelector {
+    pub fn new(value: &str) -> Self {
+        Self {
+            value: StrTendril::from_slice(value),
+        }
+    }
+}
+
+impl Selector for ConstSelector {
+    fn select<'a>(&self, _: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        vec![ElementOrTextRef::new_phantom_from_txt(self.value.clone())]
+    }
+}
+
+/// TagNameSelector replaces each element with a PhantomText of its lowercase tag name, and drops
+/// Text and PhantomText nodes, and the nameless document root
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagNameSelector;
+
+impl TagNameSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for TagNameSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        let tag_name = |e: &ElementRef| {
+            e.name().map(|n| {
+                ElementOrTextRef::new_phantom_from_txt(StrTendril::from_slice(
+                    &n.local.to_ascii_lowercase(),
+                ))
+            })
+        };
+
+        match node {
+            ElementOrTextRef::Element(e) => tag_name(&e).into_iter().collect(),
+            ElementOrTextRef::PhantomElement(e) => tag_name(&e.element()).into_iter().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// RepeatSelector duplicates any node n times
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatSelector {
+    n: usize,
+}
+
+impl RepeatSelector {
+    pub fn new(n: usize) -> Self {
+        Self { n }
+    }
+}
+
+impl Selector for RepeatSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        std::iter::repeat_n(node, self.n).collect()
+    }
+}
+
+/// IndexSelector is a batch-level selector replacing each node with a PhantomText of its 0-based
+/// index in the whole node set. A single node selected alone gets index 0.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexSelector;
+
+impl IndexSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for IndexSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        (0..nodes.len())
+            .map(|i| ElementOrTextRef::new_phantom_from_txt(StrTendril::from(i.to_string())))
+            .collect()
+    }
+}
+
+/// UniqueTextSelector is a batch-level selector keeping the first Text or PhantomText node of each
+/// distinct text, preserving order. Element and PhantomElement nodes are passed through.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UniqueTextSelector;
+
+impl UniqueTextSelector {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Selector for UniqueTextSelector {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+        self.select_batch(vec![node])
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn select_batch<'a>(&self, nodes: Vec<ElementOrTextRef<'a>>) -> Vec<ElementOrTextRef<'a>> {
+        let mut seen = HashSet::new();
+        nodes
+            .into_iter()
+            .filter(|n| match n {
+                ElementOrTextRef::Text(_) | ElementOrTextRef::PhantomText(_) => {
+                    seen.insert(n.as_text_cow().into_owned())
                 }
+                ElementOrTextRef::Element(_) | ElementOrTextRef::PhantomElement(_) => true,
             })
             .collect()
     }
 }
 
 /// NthChildSelector will filter out Text nodes, PhantomText nodes and Element nodes without sufficient children
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NthChildSelector {
     n: usize,
     reversed: bool,
@@ -132,7 +872,7 @@ impl NthChildSelector {
 }
 
 impl Selector for NthChildSelector {
-    fn select<'a, 'b: 'a>(&'b self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
+    fn select<'a>(&self, node: ElementOrTextRef<'a>) -> Vec<ElementOrTextRef<'a>> {
         std::iter::once(node)
             .filter_map(|n| match n {
                 ElementOrTextRef::Element(e) => e.children(self.reversed).nth(self.n),
@@ -141,3 +881,599 @@ impl Selector for NthChildSelector {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        html::{Html, HtmlBuilder},
+        querier::Querier,
+    };
+
+    fn query_texts(hql: &str, doc: &Html) -> Vec<String> {
+        let q = Querier::try_parse(hql).unwrap();
+        q.query_document(doc)
+            .iter()
+            .map(|n| n.node().data.as_text().unwrap().text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_text() {
+        let doc = Html::parse_fragment("<p>a<b>b</b></p><p>c</p>", false);
+
+        // elements
+        assert_eq!(query_texts("@path(`//p`) | #text()", &doc), vec!["ab", "c"]);
+        // text nodes are kept, also mixed with elements
+        assert_eq!(
+            query_texts("@path(`//p`) | @child(0) | #text()", &doc),
+            vec!["a", "c"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | @flat() | #text()", &doc),
+            vec!["ab", "a", "b", "b", "c", "c"]
+        );
+        // phantom text and phantom elements
+        assert_eq!(
+            query_texts("@path(`//b`) | #text() | #text()", &doc),
+            vec!["b"]
+        );
+        assert_eq!(query_texts("#const(`x`) | #text()", &doc), vec!["x"]);
+        assert_eq!(
+            query_texts("@path(`//b`) | @wrap(`i`) | #text()", &doc),
+            vec!["b"]
+        );
+        // the document root
+        assert_eq!(query_texts("#text()", &doc), vec!["abc"]);
+    }
+
+    #[test]
+    fn test_text_blocks() {
+        let doc = Html::parse_fragment(
+            "<div><h1>Title</h1>\n  <p>Hello <b>world</b>,\n again</p><p>Line<br>break</p>\
+             <ul><li>one</li><li>two <i>2</i></li></ul>tail</div>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//div`) | #text(` `, `\n\n`)", &doc),
+            vec!["Title\n\nHello world, again\n\nLine\n\nbreak\n\none\n\ntwo 2\n\ntail"]
+        );
+        // block separator defaults to the inline one
+        assert_eq!(
+            query_texts("@path(`//ul`) | #text(`|`)", &doc),
+            vec!["one|two|2"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text(` `, `\n`)", &doc),
+            vec!["Hello world, again", "Line\nbreak"]
+        );
+        // text nodes are kept as is
+        assert_eq!(
+            query_texts("@path(`//b`) | @child(0) | #text(` `, `\n`)", &doc),
+            vec!["world"]
+        );
+
+        // text nodes in a block are not separated by markup alone
+        let doc = Html::parse_fragment("<p><b>w</b>ord <i> x </i>\ty</p>", false);
+        assert_eq!(
+            query_texts("@path(`//p`) | #text(`_`, `\n`)", &doc),
+            vec!["word_x_y"]
+        );
+    }
+
+    #[test]
+    fn test_leaves() {
+        let doc = Html::parse_fragment(
+            "<div>a<p>b <b>c</b></p><!--x--><template>t</template><p></p>d</div>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//div`) | #leaves()", &doc),
+            vec!["a", "b ", "c", "d"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #leaves()", &doc),
+            vec!["b ", "c"]
+        );
+        // text yields itself
+        assert_eq!(
+            query_texts("@path(`//b`) | @wrap(`i`) | #leaves() | #leaves()", &doc),
+            vec!["c"]
+        );
+        assert_eq!(
+            query_texts("@path(`//div`) | @child(0) | #leaves()", &doc),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn test_text_all() {
+        let doc = Html::parse_fragment("<p>a<b>b</b></p><p>c</p>text<p></p>", false);
+
+        assert_eq!(query_texts("@path(`//p`) | #textAll()", &doc), vec!["abc"]);
+        assert_eq!(
+            query_texts("@path(`//p`) | #textAll(`, `)", &doc),
+            vec!["ab, c, "]
+        );
+        // mixed with text nodes
+        assert_eq!(
+            query_texts("@path(`//p`) | @flat() | #textAll(`|`)", &doc),
+            vec!["ab|a|b|b|c|c|"]
+        );
+        assert!(query_texts("@path(`//li`) | #textAll()", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_text_len() {
+        let doc = Html::parse_fragment("<div>ab<p>café<b>!</b></p><p></p></div>", false);
+
+        assert_eq!(query_texts("@path(`//div`) | #textLen()", &doc), vec!["7"]);
+        assert_eq!(
+            query_texts("@path(`//p`) | #textLen()", &doc),
+            vec!["5", "0"]
+        );
+        // text nodes count their own text
+        assert_eq!(
+            query_texts("@path(`//div`) | @child(0) | #textLen()", &doc),
+            vec!["2"]
+        );
+        assert_eq!(
+            query_texts("@path(`//b`) | @wrap(`i`) | #textLen()", &doc),
+            vec!["1"]
+        );
+    }
+
+    #[test]
+    fn test_pi() {
+        let doc = Html::builder()
+            .processing_instruction("xml-stylesheet", r#"href="a.xsl""#)
+            .child(
+                HtmlBuilder::new()
+                    .element("rss")
+                    .processing_instruction("cache", "max-age=60")
+                    .text("x"),
+            )
+            .build();
+
+        assert_eq!(
+            query_texts("@pi(`xml-stylesheet`)", &doc),
+            vec![r#"href="a.xsl""#]
+        );
+        assert_eq!(
+            query_texts("@pi()", &doc),
+            vec![r#"href="a.xsl""#, "max-age=60"]
+        );
+        assert_eq!(
+            query_texts("@path(`//rss`) | @pi()", &doc),
+            vec!["max-age=60"]
+        );
+        assert!(query_texts("@pi(`xml`)", &doc).is_empty());
+        // the HTML parser makes comments of processing instructions
+        let doc = Html::parse_document(r#"<?xml-stylesheet href="a.xsl"?><p>x</p>"#, false);
+        assert!(query_texts("@pi()", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_text_slice() {
+        let doc = Html::parse_fragment("<p>ID-12345678-x</p><p>日本語テキスト</p>", false);
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #slice(3, 11)", &doc),
+            vec!["12345678", "テキスト"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #slice(-2, 100)", &doc),
+            vec!["-x", "スト"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #slice(-100, -2)", &doc),
+            vec!["ID-12345678", "日本語テキ"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #slice(5, 2)", &doc),
+            vec!["", ""]
+        );
+    }
+
+    #[test]
+    fn test_replace_literal() {
+        let doc = Html::parse_fragment("<p>a.b</p><p>a.b.c.d</p><p>abc</p>", false);
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #replaceLiteral(`.`, ` - `)", &doc),
+            vec!["a - b", "a - b - c - d", "abc"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #replaceLiteral(`b.`, ``)", &doc),
+            vec!["a.b", "a.c.d", "abc"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #replaceLiteral(`x`, `y`)", &doc),
+            vec!["a.b", "a.b.c.d", "abc"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #replaceLiteral(``, `y`)", &doc),
+            vec!["a.b", "a.b.c.d", "abc"]
+        );
+    }
+
+    #[test]
+    fn test_eq() {
+        let doc = Html::parse_fragment(
+            "<a>Prev</a><a>Next</a><a> Next </a><a>next</a><a>Next page</a>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//a`) | #text() | #eq(`Next`)", &doc),
+            vec!["Next"]
+        );
+        assert_eq!(
+            query_texts("@path(`//a`) | #text() | #eq(`Next`, 1)", &doc),
+            vec!["Next", " Next "]
+        );
+        assert_eq!(
+            query_texts("@path(`//a`) | #text() | #eq(`Next`, 0, 0)", &doc),
+            vec!["Next", "next"]
+        );
+        assert_eq!(
+            query_texts("@path(`//a`) | #text() | #eq(`NEXT`, 1, 0)", &doc),
+            vec!["Next", " Next ", "next"]
+        );
+        // elements are compared with their text and kept as elements
+        let q = Querier::try_parse("@path(`//a`) | #eq(`Next`, 1)").unwrap();
+        assert_eq!(
+            Querier::results_to_string(&q.query_document(&doc), ","),
+            "<a>Next</a>,<a> Next </a>"
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        let doc = Html::parse_fragment("<p>\tC:\\dir\n</p>", false);
+
+        assert_eq!(
+            query_texts(
+                r"@path(`//p`) | #text() | #trimPrefix(`\t`) | #trimSuffix(`\n`)",
+                &doc
+            ),
+            vec!["C:\\dir"]
+        );
+        assert_eq!(
+            query_texts(
+                r"@path(`//p`) | #text() | #replaceLiteral(`\\`, `/`) | #replaceLiteral(`\t`, `\``)",
+                &doc
+            ),
+            vec!["`C:/dir\n"]
+        );
+
+        // a trailing backslash escapes the closing backtick unless doubled
+        assert!(Querier::try_parse(r"#const(`a\`)").is_err());
+        assert_eq!(query_texts(r"#const(`a\\`)", &doc), vec!["a\\"]);
+
+        // regexes are passed as written, so `\\` is a literal backslash
+        let doc = Html::parse_fragment(r#"<h1 title="C:\dir\42">x</h1>"#, false);
+        assert_eq!(
+            query_texts(r"@path(`//h1`) | @matchAttr(`title`, `\\(\d+)$`, 1)", &doc),
+            vec!["42"]
+        );
+        assert_eq!(
+            query_texts(r"@path(`//~`(\\|h)1``) | #text()", &doc),
+            vec!["x"]
+        );
+    }
+
+    #[test]
+    fn test_unique_text() {
+        let doc = Html::parse_fragment("<p>b</p><p>a</p><p>b</p><p>c</p><p>a</p><p>B</p>", false);
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #unique()", &doc),
+            vec!["b", "a", "c", "B"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #const(`x`) | #unique()", &doc),
+            vec!["x"]
+        );
+    }
+
+    #[test]
+    fn test_capitalize() {
+        let doc = Html::parse_fragment(
+            "<p>hello</p><p>  the quick\tbrown fox</p><p>Already Done</p><p>élan vital</p>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #capitalize()", &doc),
+            vec![
+                "Hello",
+                "  The quick\tbrown fox",
+                "Already Done",
+                "Élan vital"
+            ]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #titlecase()", &doc),
+            vec![
+                "Hello",
+                "  The Quick\tBrown Fox",
+                "Already Done",
+                "Élan Vital"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unix_newlines() {
+        let doc = Html::parse_fragment(r#"<p title="a&#13;&#10;b&#13;c">x</p>"#, false);
+
+        for (hql, expected) in [
+            (r"#const(`a\r\nb\r\n`) | #unixNewlines()", "a\nb\n"),
+            (r"#const(`a\rb\r\r\nc`) | #unixNewlines()", "a\nb\n\nc"),
+            (r"#const(`a\nb\n`) | #unixNewlines()", "a\nb\n"),
+            (
+                r"#const(`a\r\nb\rc`) | #unixNewlines() | #unixNewlines()",
+                "a\nb\nc",
+            ),
+            ("@path(`//p`) | #attr(`title`) | #unixNewlines()", "a\nb\nc"),
+        ] {
+            assert_eq!(query_texts(hql, &doc), vec![expected], "{hql}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let doc = Html::parse_fragment(
+            r#"<a href="b/c?q=1">x</a><a href="/root"></a><a href="../up"></a><a href="HTTPS://Other.com"></a><a href="//cdn.com/x.js"></a><a href="mailto:a@b.com"></a><a href="http://[bad"></a><a href="  #frag "></a>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_texts(
+                "@path(`//a`) | #attr(`href`) | #resolveUrl(`https://example.com/dir/page.html`)",
+                &doc
+            ),
+            vec![
+                "https://example.com/dir/b/c?q=1",
+                "https://example.com/root",
+                "https://example.com/up",
+                "https://other.com/",
+                "https://cdn.com/x.js",
+                "mailto:a@b.com",
+                "https://example.com/dir/page.html#frag",
+            ]
+        );
+
+        // elements are passed through
+        let q = Querier::try_parse("@path(`//a`) | #resolveUrl(`http://a.com`)").unwrap();
+        assert_eq!(q.query_document(&doc).len(), 8);
+    }
+
+    #[test]
+    fn test_index() {
+        let doc = Html::parse_fragment("<p>a</p><p>b</p><p>c</p>", false);
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #index()", &doc),
+            vec!["0", "1", "2"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #repeat(2) | #index()", &doc),
+            vec!["0", "1", "2", "3", "4", "5"]
+        );
+    }
+
+    #[test]
+    fn test_tag_name() {
+        let doc = Html::parse_fragment("<div>a<p>b</p>c<SPAN>d</SPAN></div>", false);
+
+        assert_eq!(
+            query_texts("@path(`//div`) | @flat() | #tagName()", &doc),
+            vec!["div", "p", "span"]
+        );
+    }
+
+    #[test]
+    fn test_const_and_repeat() {
+        let doc = Html::parse_fragment("<p>a</p><p>b</p>", false);
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #const(`-`)", &doc),
+            vec!["-", "-"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #repeat(2)", &doc),
+            vec!["a", "a", "b", "b"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #repeat(3) | #const(`x y`)", &doc),
+            vec!["x y"; 6]
+        );
+        assert!(query_texts("@path(`//p`) | #text() | #repeat(0)", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_has_text() {
+        let doc = Html::parse_fragment(
+            "<p></p><p>Hello <b>world</b></p><p><span>foo</span></p><p> </p>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//p`) | @hasText() | #text()", &doc),
+            vec!["Hello world", "foo", " "]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | @hasText(`o w`) | #text()", &doc),
+            vec!["Hello world"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | @hasText(`o`) | @child(0) | #text()", &doc),
+            vec!["Hello ", "foo"]
+        );
+        assert!(query_texts("@path(`//p`) | @hasText(`bar`)", &doc).is_empty());
+    }
+
+    #[cfg(feature = "ascii")]
+    #[test]
+    fn test_ascii_fold() {
+        let doc = Html::parse_fragment(
+            "<p>Café Crème brûlée</p><p>Ærøskøbing</p><p>北京</p><p>plain</p>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #ascii()", &doc),
+            vec!["Cafe Creme brulee", "AEroskobing", "Bei Jing", "plain"]
+        );
+        // combined with other selectors for slugs
+        assert_eq!(
+            query_texts(
+                "@path(`//p`) | @child(0) | #ascii() | #replaceLiteral(` `, `-`) | #slice(0, 4)",
+                &doc
+            ),
+            vec!["Cafe", "AEro", "Bei-", "plai"]
+        );
+    }
+
+    #[test]
+    fn test_trim_chars() {
+        let doc = Html::parse_fragment(
+            r#"<p>[[a]]</p><p>"quoted"</p><p>[ "mixed" ]</p><p>plain [x]</p><p>[]</p>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text() | #trimChars(`[]`)", &doc),
+            vec!["a", "\"quoted\"", " \"mixed\" ", "plain [x", ""]
+        );
+        assert_eq!(
+            query_texts(r#"@path(`//p`) | #text() | #trimChars(`"`)"#, &doc),
+            vec!["[[a]]", "quoted", "[ \"mixed\" ]", "plain [x]", "[]"]
+        );
+        assert_eq!(
+            query_texts(r#"@path(`//p`) | #text() | #trimChars(`[] "`)"#, &doc),
+            vec!["a", "quoted", "mixed", "plain [x", ""]
+        );
+        // escaped backtick and multi-byte chars
+        assert_eq!(
+            query_texts(r"#const(`«\`x\`»`) | #trimChars(`\`«»`)", &doc),
+            vec!["x"]
+        );
+    }
+
+    #[test]
+    fn test_title() {
+        let doc = Html::parse_document(
+            "<html><head><title> My \n Page </title></head><body><svg><title>icon</title></svg><p>a</p><p>b</p></body></html>",
+            false,
+        );
+
+        assert_eq!(query_texts("@title()", &doc), vec!["My Page"]);
+        // whatever the node set is, the title is yielded once
+        assert_eq!(
+            query_texts("@path(`//p`) | @title()", &doc),
+            vec!["My Page"]
+        );
+        // phantom nodes do not belong to the document
+        assert!(query_texts("@path(`//p`) | #text() | @title()", &doc).is_empty());
+        assert!(query_texts("@path(`//div`) | @title()", &doc).is_empty());
+
+        let doc = Html::parse_document(
+            "<html><head></head><body><svg><title>icon</title></svg></body></html>",
+            false,
+        );
+        assert!(query_texts("@title()", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_visible_text() {
+        let doc = Html::parse_fragment(
+            "<div>a</div><div> \n\t</div><div><span> </span>\u{a0}<p></p></div><div></div><div> <p><b>b</b></p></div>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//div`) | @visible() | #text() | #trim()", &doc),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            query_texts("@path(`//div`) | @child(0) | @visible()", &doc),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn test_visible_hidden() {
+        let doc = Html::parse_fragment(
+            r#"<p>shown</p><p style="color: red; DISPLAY: None">styled</p><p hidden>attr</p>
+            <p aria-hidden="true">aria</p><p aria-hidden="false">not aria</p>
+            <div style="display: block">block</div><section hidden><b>nested</b></section>"#,
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//p`) | @visible() | #text()", &doc),
+            vec!["shown", "not aria"]
+        );
+        // descendants of hidden elements are hidden, including text nodes
+        assert_eq!(
+            query_texts("#leaves() | @visible()", &doc),
+            vec!["shown", "not aria", "block"]
+        );
+        assert!(query_texts("@path(`//b`) | @visible()", &doc).is_empty());
+    }
+
+    #[test]
+    fn test_text_with_breaks() {
+        let doc = Html::parse_fragment(
+            "<p>line1<br>line2</p><div>a<hr>b<br/><span>c<br></span></div>",
+            false,
+        );
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #textWithBreaks()", &doc),
+            vec!["line1\nline2"]
+        );
+        assert_eq!(
+            query_texts("@path(`//div`) | #textWithBreaks()", &doc),
+            vec!["a\nb\nc\n"]
+        );
+        assert_eq!(query_texts("@path(`//div`) | #text()", &doc), vec!["abc"]);
+        assert_eq!(
+            query_texts("@path(`//p`) | #text()", &doc),
+            vec!["line1line2"]
+        );
+    }
+
+    #[test]
+    fn test_own_text() {
+        let doc = Html::parse_fragment("<p>Hello <b>world</b></p>", false);
+
+        assert_eq!(
+            query_texts("@path(`//p`) | #text()", &doc),
+            vec!["Hello world"]
+        );
+        assert_eq!(
+            query_texts("@path(`//p`) | #ownText()", &doc),
+            vec!["Hello "]
+        );
+    }
+
+    #[test]
+    fn test_own_text_phantom() {
+        let doc = Html::parse_fragment("<p>Hello <b>world</b></p>", false);
+
+        // the wrapper's only child is the wrapped element, so it has no own text
+        assert_eq!(
+            query_texts("@path(`//p`) | @wrap(`i`) | #ownText()", &doc),
+            vec![""]
+        );
+        assert_eq!(
+            query_texts("@path(`//b`) | @child(0) | @wrap(`i`) | #ownText()", &doc),
+            vec!["world"]
+        );
+    }
+}
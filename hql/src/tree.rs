@@ -1,8 +1,12 @@
+#[cfg(test)]
+use std::cell::Cell;
 use std::fmt::{Debug, Display};
 
 use tracing::info;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// NodeID is the index of node in the tree. Nodes are numbered in creation order, so ordering of ids
+/// follows the parse order of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NodeID(usize);
 
 impl From<usize> for NodeID {
@@ -95,6 +99,14 @@ impl<T: Debug + Display> Tree<T> {
         self.nodes.get_mut::<usize>(id.into())
     }
 
+    /// Replace data of node id in place, keeping all its parent, sibling and children links.
+    ///
+    /// Return the old data, or None if id does not exist
+    pub fn replace_data(&mut self, id: NodeID, data: T) -> Option<T> {
+        let node = self.node_mut_ref(id)?;
+        Some(std::mem::replace(&mut node.data, data))
+    }
+
     pub fn root_ref(&self) -> Option<&Node<T>> {
         self.node_ref(0.into())
     }
@@ -206,6 +218,34 @@ impl<T: Debug + Display> Tree<T> {
         self.append_child_id(target, id)
     }
 
+    /// Append a copy of the subtree of `node` in tree `src` as the last child of target
+    ///
+    /// Return reference of the copied root
+    ///
+    /// Return None if target does not exist
+    pub fn append_subtree_clone(
+        &mut self,
+        target: NodeID,
+        src: &Tree<T>,
+        node: &Node<T>,
+    ) -> Option<&Node<T>>
+    where
+        T: Clone,
+    {
+        let root = self.append_child(target, node.data.clone())?.id;
+
+        // pairs of source node and the id of its copy
+        let mut stack = vec![(node, root)];
+        while let Some((n, copy)) = stack.pop() {
+            for (child, _) in ChildrenTraverse::new(src, n, false) {
+                let child_copy = self.append_child(copy, child.data.clone()).unwrap().id;
+                stack.push((child, child_copy));
+            }
+        }
+
+        self.node_ref(root)
+    }
+
     /// Remove all the children from node and append them to new_parent.
     pub fn reparent_from_id_append(
         &mut self,
@@ -258,9 +298,7 @@ impl<T: Debug + Display> Tree<T> {
 
     // Detach this node from its parent
     pub fn detach(&mut self, node_id: NodeID) -> Option<&Node<T>> {
-        self.node_ref(node_id)?;
-
-        let parent_id = self.parent_ref(node_id).and_then(|n| n.parent);
+        let parent_id = self.node_ref(node_id)?.parent;
 
         // only handle case that parent exists
         if let Some(parent_id) = parent_id {
@@ -321,6 +359,19 @@ impl<'a, T: Debug + Display> ChildrenTraverse<'a, T> {
             reversed,
         }
     }
+
+    /// Traverse siblings after node, or siblings before node in reverse order if reversed
+    pub fn new_siblings(tree: &'a Tree<T>, node: &'a Node<T>, reversed: bool) -> Self {
+        Self {
+            tree,
+            cur: match reversed {
+                false => node.next_sibling,
+                true => node.previous_sibling,
+            }
+            .and_then(|id| tree.node_ref(id)),
+            reversed,
+        }
+    }
 }
 
 impl<'a, T: Debug + Display> Iterator for ChildrenTraverse<'a, T> {
@@ -340,11 +391,20 @@ impl<'a, T: Debug + Display> Iterator for ChildrenTraverse<'a, T> {
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    /// Count nodes visited by PreOrderTraverse in current thread, to check laziness in tests
+    pub(crate) static PRE_ORDER_VISITED: Cell<usize> = const { Cell::new(0) };
+}
+
 pub struct PreOrderTraverse<'a, T: Debug + Display> {
     tree: &'a Tree<T>,
 
     root: &'a Node<T>,
     cur: Option<&'a Node<T>>,
+
+    // do not go into children of non-root nodes satisfying prune
+    prune: Option<fn(&Node<T>) -> bool>,
 }
 
 impl<'a, T: Debug + Display> PreOrderTraverse<'a, T> {
@@ -353,8 +413,24 @@ impl<'a, T: Debug + Display> PreOrderTraverse<'a, T> {
             tree,
             root,
             cur: Some(root),
+            prune: None,
         }
     }
+
+    /// Traverse like `new`, but skip the descendants of every node except root that prune returns true.
+    /// The pruned node itself is still visited.
+    pub fn new_pruned(tree: &'a Tree<T>, root: &'a Node<T>, prune: fn(&Node<T>) -> bool) -> Self {
+        Self {
+            tree,
+            root,
+            cur: Some(root),
+            prune: Some(prune),
+        }
+    }
+
+    fn pruned(&self, node: &Node<T>) -> bool {
+        node.id != self.root.id && self.prune.is_some_and(|p| p(node))
+    }
 }
 
 impl<'a, T: Debug + Display> Iterator for PreOrderTraverse<'a, T> {
@@ -363,13 +439,17 @@ impl<'a, T: Debug + Display> Iterator for PreOrderTraverse<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         let cur = self.cur?;
         info!("visit: {:?}", cur);
+        #[cfg(test)]
+        PRE_ORDER_VISITED.with(|v| v.set(v.get() + 1));
 
-        match cur.children {
+        match cur.children.filter(|_| !self.pruned(cur)) {
             Some((first, _)) => {
                 info!("{} has children. go to next depth", first);
                 self.cur = self.tree.node_ref(first);
             }
 
+            // the traversal ends at root if root has no children to visit
+            None if cur.id == self.root.id => self.cur = None,
             None => {
                 match cur.next_sibling.and_then(|n| self.tree.node_ref(n)) {
                     Some(sib) => {
@@ -416,7 +496,7 @@ impl<'a, T: Debug + Display> Iterator for PreOrderTraverse<'a, T> {
 mod test {
     use crate::tree::ChildrenTraverse;
 
-    use super::{PreOrderTraverse, Tree};
+    use super::{NodeID, PreOrderTraverse, Tree};
 
     #[test]
     fn test_tree_preorder_traverse() {
@@ -443,7 +523,154 @@ mod test {
             node_values, preorder_values,
             "want: {:?}, get: {:?}",
             preorder_values, node_values,
-        )
+        );
+
+        // traversal rooted at a leaf should not escape to its siblings
+        let node_values = PreOrderTraverse::new(&tree, tree.node_ref(node5).unwrap())
+            .map(|(n, _)| n.data)
+            .collect::<Vec<_>>();
+        assert_eq!(node_values, vec![5, 6]);
+        let node10 = tree.append_child(node5, 10).unwrap().id;
+        let node_values = PreOrderTraverse::new(&tree, tree.node_ref(node10).unwrap())
+            .map(|(n, _)| n.data)
+            .collect::<Vec<_>>();
+        assert_eq!(node_values, vec![10]);
+    }
+
+    #[test]
+    fn test_tree_pruned_preorder_traverse() {
+        let mut tree = Tree::new(0);
+        let root = tree.root_ref().unwrap().id;
+
+        let node1 = tree.append_child(root, 1).unwrap().id;
+        let node2 = tree.append_child(root, -2).unwrap().id;
+        tree.append_child(node1, -3).unwrap();
+        tree.append_child(node2, 4).unwrap();
+        tree.append_child(root, 5).unwrap();
+
+        let node_values =
+            PreOrderTraverse::new_pruned(&tree, tree.root_ref().unwrap(), |n| n.data < 0)
+                .map(|(n, _)| n.data)
+                .collect::<Vec<_>>();
+        assert_eq!(node_values, vec![0, 1, -3, -2, 5]);
+
+        // root is never pruned
+        let node_values =
+            PreOrderTraverse::new_pruned(&tree, tree.node_ref(node2).unwrap(), |n| n.data < 0)
+                .map(|(n, _)| n.data)
+                .collect::<Vec<_>>();
+        assert_eq!(node_values, vec![-2, 4]);
+    }
+
+    #[test]
+    fn test_node_id_ord() {
+        let mut ids = [5, 0, 3, usize::MAX, 1, 4]
+            .into_iter()
+            .map(NodeID::from)
+            .collect::<Vec<_>>();
+        ids.sort();
+
+        assert_eq!(
+            ids,
+            [0, 1, 3, 4, 5, usize::MAX]
+                .into_iter()
+                .map(NodeID::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_tree_append_subtree_clone() {
+        let mut src = Tree::new("root");
+        src.append_child(0.into(), "a");
+        src.append_child(1.into(), "b");
+        src.append_child(1.into(), "c");
+        src.append_child(2.into(), "d");
+
+        let mut dst = Tree::new("wrapper");
+        let copied = dst
+            .append_subtree_clone(0.into(), &src, src.node_ref(1.into()).unwrap())
+            .unwrap()
+            .id;
+        assert_eq!(copied, 1.into());
+        assert_eq!(
+            PreOrderTraverse::new(&dst, dst.root_ref().unwrap())
+                .map(|(n, _)| n.data)
+                .collect::<Vec<_>>(),
+            vec!["wrapper", "a", "b", "d", "c"]
+        );
+
+        assert!(dst
+            .append_subtree_clone(42.into(), &src, src.root_ref().unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_tree_siblings_traverse() {
+        let mut tree = Tree::new(0);
+        let root = tree.root_ref().unwrap().id;
+        for i in 1..=4 {
+            tree.append_child(root, i);
+        }
+
+        let middle = tree.node_ref(2.into()).unwrap();
+        let values = |reversed| {
+            ChildrenTraverse::new_siblings(&tree, middle, reversed)
+                .map(|(n, _)| n.data)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(false), vec![3, 4]);
+        assert_eq!(values(true), vec![1]);
+
+        let root = tree.root_ref().unwrap();
+        assert_eq!(
+            ChildrenTraverse::new_siblings(&tree, root, false).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_tree_detach() {
+        let mut tree = Tree::new(0);
+        let root = tree.root_ref().unwrap().id;
+        let node1 = tree.append_child(root, 1).unwrap().id;
+        for i in 2..=4 {
+            tree.append_child(node1, i);
+        }
+
+        let values = |tree: &Tree<i32>| {
+            PreOrderTraverse::new(tree, tree.root_ref().unwrap())
+                .map(|(n, _)| n.data)
+                .collect::<Vec<_>>()
+        };
+
+        // first, middle and last children of a non-root node
+        tree.detach(3.into());
+        assert_eq!(values(&tree), vec![0, 1, 2, 4]);
+        tree.detach(2.into());
+        assert_eq!(values(&tree), vec![0, 1, 4]);
+        tree.detach(4.into());
+        assert_eq!(values(&tree), vec![0, 1]);
+        assert!(tree.children_range(node1).is_none());
+        assert!(tree.parent_ref(4.into()).is_none());
+    }
+
+    #[test]
+    fn test_tree_replace_data() {
+        let mut tree = Tree::new(0);
+        let root = tree.root_ref().unwrap().id;
+
+        let node1 = tree.append_child(root, 1).unwrap().id;
+        tree.append_child(root, 2).unwrap();
+        tree.append_child(node1, 3).unwrap();
+
+        assert_eq!(tree.replace_data(node1, 10), Some(1));
+        assert_eq!(tree.replace_data(usize::MAX.into(), 10), None);
+
+        let node_values = PreOrderTraverse::new(&tree, tree.root_ref().unwrap())
+            .map(|(n, _)| n.data)
+            .collect::<Vec<_>>();
+        assert_eq!(node_values, vec![0, 10, 3, 2]);
     }
 
     #[test]